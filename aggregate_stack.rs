@@ -0,0 +1,250 @@
+#![allow(dead_code)]
+#![allow(warnings)]
+//! Stack with running aggregates.
+/// # Aggregate stack
+/// A LIFO stack like rustack.rs that also answers "what is the min/max/sum/gcd
+/// of everything on the stack" in O(1).
+/// Next to `data` it keeps `aggs`, where `aggs[i]` is the aggregate of
+/// `data[0..=i]`. A push combines the new item with the aggregate below it,
+/// a pop simply drops the top aggregate.
+use std::marker::PhantomData;
+use std::ops::Add;
+
+/// How the items of an `AggregateStack` are folded.
+/// `combine` must be associative, e.g. min, max, sum or gcd.
+trait Aggregate<T> {
+    type Output: Clone;
+    // Aggregate of a single item.
+    fn single(val: &T) -> Self::Output;
+    // Aggregate of everything below plus `val`.
+    fn combine(acc: &Self::Output, val: &T) -> Self::Output;
+}
+
+struct Min;
+impl<T: Ord + Clone> Aggregate<T> for Min {
+    type Output = T;
+    fn single(val: &T) -> T { val.clone() }
+    fn combine(acc: &T, val: &T) -> T { acc.clone().min(val.clone()) }
+}
+
+struct Max;
+impl<T: Ord + Clone> Aggregate<T> for Max {
+    type Output = T;
+    fn single(val: &T) -> T { val.clone() }
+    fn combine(acc: &T, val: &T) -> T { acc.clone().max(val.clone()) }
+}
+
+/// Minimum and maximum together, as (min, max).
+struct MinMax;
+impl<T: Ord + Clone> Aggregate<T> for MinMax {
+    type Output = (T, T);
+    fn single(val: &T) -> (T, T) { (val.clone(), val.clone()) }
+    fn combine(acc: &(T, T), val: &T) -> (T, T) {
+        (acc.0.clone().min(val.clone()), acc.1.clone().max(val.clone()))
+    }
+}
+
+struct Sum;
+impl<T: Add<Output = T> + Clone> Aggregate<T> for Sum {
+    type Output = T;
+    fn single(val: &T) -> T { val.clone() }
+    fn combine(acc: &T, val: &T) -> T { acc.clone() + val.clone() }
+}
+
+struct Gcd;
+impl Aggregate<u64> for Gcd {
+    type Output = u64;
+    fn single(val: &u64) -> u64 { *val }
+    fn combine(acc: &u64, val: &u64) -> u64 {
+        let (mut a, mut b) = (*acc, *val);
+        while b != 0 {
+            let r = a % b;
+            a = b;
+            b = r;
+        }
+        a
+    }
+}
+
+#[derive(Debug)]
+struct AggregateStack<T, A: Aggregate<T>> {
+    // Size of the stack.
+    size: usize,
+    // Storage
+    data: Vec<T>,
+    // Running aggregates, one per item.
+    aggs: Vec<A::Output>,
+    kind: PhantomData<A>,
+}
+
+/// Stack tracking its minimum and maximum.
+type MinMaxStack<T> = AggregateStack<T, MinMax>;
+
+impl<T, A: Aggregate<T>> AggregateStack<T, A> {
+    fn new() -> Self {
+        Self {
+            size: 0,
+            data: Vec::new(),
+            aggs: Vec::new(),
+            kind: PhantomData,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn clear(&mut self) {
+        self.size = 0;
+        self.data.clear();
+        self.aggs.clear();
+    }
+
+    // Adding an item on top of the stack, O(1).
+    fn push(&mut self, val: T) {
+        let agg = match self.aggs.last() {
+            Some(acc) => A::combine(acc, &val),
+            None => A::single(&val),
+        };
+        self.aggs.push(agg);
+        self.data.push(val);
+        self.size += 1;
+    }
+
+    // Removing the top item from the stack, O(1).
+    fn pop(&mut self) -> Option<T> {
+        if self.size == 0 {
+            return None;
+        }
+        self.size -= 1;
+        self.aggs.pop();
+        self.data.pop()
+    }
+
+    // Returning reference to the top value.
+    // There is no `peek_mut`: changing an item would make the aggregates
+    // above it stale.
+    fn peek(&self) -> Option<&T> {
+        if self.size == 0 {
+            return None;
+        }
+        self.data.get(self.size - 1)
+    }
+
+    // Aggregate of everything on the stack, O(1).
+    fn aggregate(&self) -> Option<&A::Output> {
+        self.aggs.last()
+    }
+
+    // Getting immutable stack iterator, top first.
+    fn iter(&self) -> Iter<T> {
+        let mut iterator = Iter { stack: Vec::new() };
+        for item in self.data.iter() {
+            iterator.stack.push(item);
+        }
+        iterator
+    }
+}
+
+impl<T: Ord + Clone> AggregateStack<T, MinMax> {
+    fn min(&self) -> Option<&T> {
+        self.aggregate().map(|agg| &agg.0)
+    }
+
+    fn max(&self) -> Option<&T> {
+        self.aggregate().map(|agg| &agg.1)
+    }
+}
+
+struct Iter<'a, T: 'a> { stack: Vec<&'a T>, }
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop()
+    }
+}
+
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Lifo<T>: Container {
+    fn push(&mut self, val: T) -> Result<(), String>;
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<&T>;
+}
+
+impl<T, A: Aggregate<T>> Container for AggregateStack<T, A> {
+    fn len(&self) -> usize { AggregateStack::len(self) }
+    fn clear(&mut self) { AggregateStack::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T, A: Aggregate<T>> Lifo<T> for AggregateStack<T, A> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        AggregateStack::push(self, val);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> { AggregateStack::pop(self) }
+    fn peek(&self) -> Option<&T> { AggregateStack::peek(self) }
+}
+
+// Move every item of `from` onto `to`, for any two stacks.
+fn transfer<T, S: Lifo<T>, D: Lifo<T>>(from: &mut S, to: &mut D) -> Result<(), String> {
+    while let Some(val) = from.pop() {
+        to.push(val)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    minmax_ops();
+    aggregate_ops();
+
+    fn minmax_ops() {
+        println!("MIN MAX STACK");
+        let mut s: MinMaxStack<i32> = MinMaxStack::new();
+        for price in [105, 101, 110, 99, 120] {
+            s.push(price);
+            println!("push {:3} -> min: {:?}, max: {:?}", price, s.min(), s.max());
+        }
+        while let Some(price) = s.pop() {
+            println!("pop {:3}  -> min: {:?}, max: {:?}", price, s.min(), s.max());
+        }
+        println!("================================================");
+    }
+
+    fn aggregate_ops() {
+        println!("OTHER AGGREGATES");
+        let mut sum: AggregateStack<i64, Sum> = AggregateStack::new();
+        let mut gcd: AggregateStack<u64, Gcd> = AggregateStack::new();
+        let mut min: AggregateStack<&str, Min> = AggregateStack::new();
+        for (n, name) in [(12, "Leonhard"), (18, "Euler"), (30, "Joseph"), (7, "Fourier")] {
+            sum.push(n as i64); gcd.push(n); min.push(name);
+        }
+        println!("sum: {:?}, gcd: {:?}, min: {:?}", sum.aggregate(), gcd.aggregate(), min.aggregate());
+        sum.pop(); gcd.pop(); min.pop();
+        println!("after pop -> sum: {:?}, gcd: {:?}, min: {:?}",
+                 sum.aggregate(), gcd.aggregate(), min.aggregate());
+        print!("The iter() test is: ");
+        for item in sum.iter() {
+            print!("{}, ", item);
+        }
+        let mut bounds: MinMaxStack<i64> = MinMaxStack::new();
+        transfer(&mut sum, &mut bounds).unwrap();
+        println!("\nThrough the Lifo trait -> min: {:?}, max: {:?}, left: {}",
+                 bounds.min(), bounds.max(), Container::len(&sum));
+        assert_eq!((bounds.min(), bounds.max()), (Some(&12), Some(&30)));
+        println!("================================================");
+    }
+}
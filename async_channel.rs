@@ -0,0 +1,387 @@
+//! Async channel.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// A bounded multi-producer, single-consumer channel for async code, with no
+/// runtime dependency. Items go through the `Queue<T>` of rusqueue.rs, its
+/// capacity check gives the backpressure: a `send` on a full queue stays
+/// pending until the receiver makes room.
+///
+/// `send` and `recv` return futures written by hand, they work with any
+/// executor and can be `.await`ed from async code. A waiting task registers
+/// its `Waker` and is woken when the other side makes progress.
+/// The channel closes when every `Sender` is dropped: `recv` drains what
+/// is left and then gives None.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+#[derive(Debug)]
+struct Queue<T> {
+    cap: usize, // Capacity
+    data: Vec<T>, // Storing elements
+}
+
+impl<T> Queue<T> {
+    fn new(size: usize) -> Self {
+        Self { cap: size, data: Vec::with_capacity(size) }
+    }
+
+    fn is_empty(&self) -> bool { Self::len(&self) == 0 }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+    fn len(&self) -> usize { self.data.len() }
+
+    // enqueue or add data to the queue
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        if self.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    // pop out values.
+    fn dequeue(&mut self) -> Option<T> {
+        if self.len() > 0 {
+            self.data.pop()
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: Queue<T>,
+    senders: usize,
+    receiver_alive: bool,
+    // Tasks waiting for space, one slot per pending `SendFuture`, and the
+    // receiver waiting for an item.
+    send_wakers: Vec<Option<Waker>>,
+    free_slots: Vec<usize>,
+    recv_waker: Option<Waker>,
+}
+
+struct Channel<T> {
+    shared: Mutex<Shared<T>>,
+}
+
+impl<T> Channel<T> {
+    fn lock(&self) -> MutexGuard<Shared<T>> {
+        self.shared.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Sending half, clone it for more producers.
+struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// Receiving half.
+struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+}
+
+// A channel holding at most `cap` items in flight.
+fn channel<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Channel {
+        shared: Mutex::new(Shared {
+            queue: Queue::new(cap),
+            senders: 1,
+            receiver_alive: true,
+            send_wakers: Vec::new(),
+            free_slots: Vec::new(),
+            recv_waker: None,
+        }),
+    });
+    (Sender { chan: Arc::clone(&chan) }, Receiver { chan })
+}
+
+impl<T> Sender<T> {
+    // Resolves once the item is queued, or hands it back if the receiver
+    // is gone.
+    fn send(&self, val: T) -> SendFuture<T> {
+        SendFuture { chan: &self.chan, val: Some(val), slot: None }
+    }
+
+    // Queue the item only if there is space right now.
+    fn try_send(&self, val: T) -> Result<(), T> {
+        let mut shared = self.chan.lock();
+        if !shared.receiver_alive || shared.queue.is_full() {
+            return Err(val);
+        }
+        let _ = shared.queue.enqueue(val);
+        if let Some(waker) = shared.recv_waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.lock().senders += 1;
+        Self { chan: Arc::clone(&self.chan) }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    // The last sender closes the channel, a waiting receiver must hear it.
+    fn drop(&mut self) {
+        let mut shared = self.chan.lock();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            if let Some(waker) = shared.recv_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    // Resolves with the next item, or None once the channel is closed and
+    // drained.
+    fn recv(&mut self) -> RecvFuture<T> {
+        RecvFuture { chan: &self.chan }
+    }
+
+    // Take an item only if one is queued right now.
+    fn try_recv(&mut self) -> Option<T> {
+        let mut shared = self.chan.lock();
+        let val = shared.queue.dequeue();
+        if val.is_some() {
+            wake_senders(&mut shared);
+        }
+        val
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    // Waiting senders fail instead of hanging forever.
+    fn drop(&mut self) {
+        let mut shared = self.chan.lock();
+        shared.receiver_alive = false;
+        wake_senders(&mut shared);
+    }
+}
+
+// The slots stay with their futures, which register again when polled.
+fn wake_senders<T>(shared: &mut Shared<T>) {
+    for slot in shared.send_wakers.iter_mut() {
+        if let Some(waker) = slot.take() {
+            waker.wake();
+        }
+    }
+}
+
+// Keep `waker` in `slot`. Polling again with the same waker, as after a
+// spurious wakeup, keeps the one already there.
+fn register(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(old) if old.will_wake(waker) => {}
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+/// Future returned by `Sender::send`.
+struct SendFuture<'a, T> {
+    chan: &'a Channel<T>,
+    val: Option<T>,
+    // Its entry in `send_wakers` while it waits.
+    slot: Option<usize>,
+}
+
+impl<'a, T> SendFuture<'a, T> {
+    fn release(&mut self, shared: &mut Shared<T>) {
+        if let Some(slot) = self.slot.take() {
+            shared.send_wakers[slot] = None;
+            shared.free_slots.push(slot);
+        }
+    }
+}
+
+impl<'a, T> Drop for SendFuture<'a, T> {
+    // A future dropped while waiting gives its slot back.
+    fn drop(&mut self) {
+        if self.slot.is_some() {
+            let chan = self.chan;
+            self.release(&mut chan.lock());
+        }
+    }
+}
+
+// The item is only moved out, never pinned in place.
+impl<'a, T> Unpin for SendFuture<'a, T> {}
+
+impl<'a, T> Future for SendFuture<'a, T> {
+    type Output = Result<(), T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let chan = this.chan;
+        let mut shared = chan.lock();
+        let val = this.val.take().expect("SendFuture polled after completion");
+        if !shared.receiver_alive {
+            this.release(&mut shared);
+            return Poll::Ready(Err(val));
+        }
+        if shared.queue.is_full() {
+            // Backpressure: wait until the receiver takes something.
+            let slot = match this.slot {
+                Some(slot) => slot,
+                None => match shared.free_slots.pop() {
+                    Some(slot) => slot,
+                    None => {
+                        shared.send_wakers.push(None);
+                        shared.send_wakers.len() - 1
+                    }
+                },
+            };
+            this.slot = Some(slot);
+            register(&mut shared.send_wakers[slot], cx.waker());
+            this.val = Some(val);
+            return Poll::Pending;
+        }
+        this.release(&mut shared);
+        let _ = shared.queue.enqueue(val);
+        if let Some(waker) = shared.recv_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Future returned by `Receiver::recv`.
+struct RecvFuture<'a, T> {
+    chan: &'a Channel<T>,
+}
+
+impl<'a, T> Future for RecvFuture<'a, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.chan.lock();
+        if let Some(val) = shared.queue.dequeue() {
+            wake_senders(&mut shared);
+            return Poll::Ready(Some(val));
+        }
+        if shared.senders == 0 {
+            return Poll::Ready(None);
+        }
+        register(&mut shared.recv_waker, cx.waker());
+        Poll::Pending
+    }
+}
+
+// TINY EXECUTOR.
+
+// Waking unparks the thread running `block_on`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Run a future to completion on the current thread.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = Box::pin(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn main() {
+    basic_ops();
+    backpressure_ops();
+    spurious_ops();
+    close_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let (tx, mut rx) = channel(2);
+        block_on(tx.send("Joseph")).unwrap();
+        block_on(tx.send("Fourier")).unwrap();
+        println!("try_send when full: {:?}", tx.try_send("Euler"));
+        println!("recv: {:?}", block_on(rx.recv()));
+        println!("try_recv: {:?}", rx.try_recv());
+        println!("=========================");
+    }
+
+    fn backpressure_ops() {
+        println!("BACKPRESSURE");
+        // Producers run ahead until the two slots are full, then wait.
+        let (tx, mut rx) = channel(2);
+        let producers: Vec<_> = (0..3).map(|p| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for i in 0..100 {
+                    block_on(tx.send(p * 100 + i)).unwrap();
+                }
+            })
+        }).collect();
+        drop(tx);
+        let mut sum = 0;
+        let mut count = 0;
+        while let Some(val) = block_on(rx.recv()) {
+            sum += val;
+            count += 1;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        println!("received {} items, sum {}, expected {}", count, sum, (0..300).sum::<i32>());
+        println!("=========================");
+    }
+
+    fn spurious_ops() {
+        println!("REPEATED POLLS");
+        // Polling the same waiting future again and again, as a spurious
+        // wakeup does, keeps one waker for it.
+        let (tx, mut rx) = channel(1);
+        tx.try_send(0).unwrap();
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let waiting = |tx: &Sender<i32>| tx.chan.lock().send_wakers.iter().flatten().count();
+        let mut first = Box::pin(tx.send(1));
+        let mut second = Box::pin(tx.send(2));
+        for _ in 0..100 {
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+        }
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        println!("wakers after 100 polls of one future and 1 of another: {}", waiting(&tx));
+        assert_eq!(waiting(&tx), 2);
+        drop(second);
+        let slots = tx.chan.lock().send_wakers.len();
+        println!("after dropping one: {} waiting, {} slots", waiting(&tx), slots);
+        assert_eq!(rx.try_recv(), Some(0));
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        println!("after sending: {} waiting, {} free slots", waiting(&tx), tx.chan.lock().free_slots.len());
+        // The receiver keeps a single waker too.
+        assert_eq!(rx.try_recv(), Some(1));
+        let mut recv = Box::pin(rx.recv());
+        for _ in 0..10 {
+            assert!(recv.as_mut().poll(&mut cx).is_pending());
+        }
+        println!("receiver waiting: {}", tx.chan.lock().recv_waker.is_some());
+        println!("=========================");
+    }
+
+    fn close_ops() {
+        println!("CLOSING");
+        let (tx, rx) = channel(1);
+        block_on(tx.send(1)).unwrap();
+        // The sender is parked on a full queue until the receiver goes away.
+        let waiting = thread::spawn(move || block_on(tx.send(2)));
+        thread::sleep(std::time::Duration::from_millis(20));
+        drop(rx);
+        println!("send after receiver dropped: {:?}", waiting.join().unwrap());
+        println!("=========================");
+    }
+}
@@ -0,0 +1,316 @@
+#![allow(dead_code)]
+#![allow(warnings)]
+//! Lock-free concurrent stack.
+/// # Atomic stack
+/// A Treiber stack: the top of the stack is an atomic pointer and `push` and
+/// `pop` swing it with compare-and-swap, so both only need `&self` and the
+/// stack can be shared between threads without a Mutex.
+///
+/// A popped node cannot be freed right away, another thread may still be
+/// reading it. Nodes are reclaimed with hazard pointers: before reading a
+/// node a thread publishes its address in a hazard slot, and retired nodes
+/// are only freed once no slot holds them.
+use std::cell::RefCell;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+// HAZARD POINTERS.
+
+// One slot per thread, linked into a global list that only ever grows.
+// Slots of finished threads are reused.
+struct HazardSlot {
+    ptr: AtomicPtr<()>,
+    active: AtomicBool,
+    next: *mut HazardSlot,
+}
+
+static HAZARDS: AtomicPtr<HazardSlot> = AtomicPtr::new(ptr::null_mut());
+
+// Retired nodes are freed once this many have piled up on a thread.
+const SCAN_THRESHOLD: usize = 64;
+
+fn acquire_slot() -> &'static HazardSlot {
+    // Reuse an inactive slot if there is one.
+    let mut cur = HAZARDS.load(Ordering::Acquire);
+    while !cur.is_null() {
+        let slot = unsafe { &*cur };
+        if slot.active.compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            return slot;
+        }
+        cur = slot.next;
+    }
+    // Otherwise push a new one on the list.
+    let slot = Box::into_raw(Box::new(HazardSlot {
+        ptr: AtomicPtr::new(ptr::null_mut()),
+        active: AtomicBool::new(true),
+        next: ptr::null_mut(),
+    }));
+    loop {
+        let head = HAZARDS.load(Ordering::Acquire);
+        unsafe { (*slot).next = head; }
+        if HAZARDS.compare_exchange(head, slot, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            return unsafe { &*slot };
+        }
+    }
+}
+
+// A retired node and the function that frees it. Erasing the type lets one
+// thread local list hold nodes of every `AtomicStack<T>`.
+struct Retired {
+    ptr: *mut (),
+    free: unsafe fn(*mut ()),
+}
+
+struct Local {
+    slot: &'static HazardSlot,
+    retired: RefCell<Vec<Retired>>,
+}
+
+impl Local {
+    // Free every retired node no hazard slot points to.
+    fn scan(&self) {
+        let mut hazards = Vec::new();
+        let mut cur = HAZARDS.load(Ordering::Acquire);
+        while !cur.is_null() {
+            let slot = unsafe { &*cur };
+            let p = slot.ptr.load(Ordering::SeqCst);
+            if !p.is_null() {
+                hazards.push(p);
+            }
+            cur = slot.next;
+        }
+        self.retired.borrow_mut().retain(|node| {
+            if hazards.contains(&node.ptr) {
+                true
+            } else {
+                unsafe { (node.free)(node.ptr) };
+                false
+            }
+        });
+    }
+}
+
+impl Drop for Local {
+    // A finishing thread waits until the others let go of its retired nodes.
+    fn drop(&mut self) {
+        self.slot.ptr.store(ptr::null_mut(), Ordering::SeqCst);
+        loop {
+            self.scan();
+            if self.retired.borrow().is_empty() {
+                break;
+            }
+            thread::yield_now();
+        }
+        self.slot.active.store(false, Ordering::Release);
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local {
+        slot: acquire_slot(),
+        retired: RefCell::new(Vec::new()),
+    };
+}
+
+// THE STACK.
+
+struct Node<T> {
+    // Moved out by `pop`, so freeing the node never drops it.
+    val: ManuallyDrop<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+unsafe fn free_node<T>(node: *mut ()) {
+    drop(Box::from_raw(node as *mut Node<T>));
+}
+
+struct AtomicStack<T> {
+    head: AtomicPtr<Node<T>>,
+    // Number of items, only exact while no other thread is pushing or popping.
+    size: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for AtomicStack<T> {}
+unsafe impl<T: Send> Sync for AtomicStack<T> {}
+
+impl<T> AtomicStack<T> {
+    fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            size: AtomicUsize::new(0),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    fn len(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    // Adding an item on top of the stack.
+    // The old head is never read, so no hazard pointer is needed.
+    fn push(&self, val: T) {
+        let node = Box::into_raw(Box::new(Node {
+            val: ManuallyDrop::new(val),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        loop {
+            let head = self.head.load(Ordering::Relaxed);
+            unsafe { (*node).next.store(head, Ordering::Relaxed); }
+            if self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed).is_ok() {
+                break;
+            }
+        }
+        self.size.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Removing the top item from the stack.
+    fn pop(&self) -> Option<T> {
+        LOCAL.with(|local| {
+            let hazard = &local.slot.ptr;
+            let node = loop {
+                let head = self.head.load(Ordering::Acquire);
+                if head.is_null() {
+                    return None;
+                }
+                // Publish the hazard, then make sure head did not change in
+                // between. From here on `head` cannot be freed.
+                hazard.store(head as *mut (), Ordering::SeqCst);
+                if self.head.load(Ordering::SeqCst) != head {
+                    continue;
+                }
+                let next = unsafe { (*head).next.load(Ordering::Acquire) };
+                if self.head.compare_exchange(head, next, Ordering::SeqCst, Ordering::Relaxed).is_ok() {
+                    break head;
+                }
+            };
+            hazard.store(ptr::null_mut(), Ordering::SeqCst);
+            self.size.fetch_sub(1, Ordering::Relaxed);
+
+            // Only this thread unlinked the node, so only it takes the value.
+            let val = unsafe { ptr::read(&*(*node).val) };
+            local.retired.borrow_mut().push(Retired { ptr: node as *mut (), free: free_node::<T> });
+            if local.retired.borrow().len() >= SCAN_THRESHOLD {
+                local.scan();
+            }
+            Some(val)
+        })
+    }
+}
+
+impl<T> Drop for AtomicStack<T> {
+    // No other thread can hold a reference now, nodes are freed directly.
+    fn drop(&mut self) {
+        let mut cur = *self.head.get_mut();
+        while !cur.is_null() {
+            let mut node = unsafe { Box::from_raw(cur) };
+            unsafe { ManuallyDrop::drop(&mut node.val); }
+            cur = *node.next.get_mut();
+        }
+    }
+}
+
+fn main() {
+    basic_ops();
+    buffer_pool_ops();
+    stress_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let s = AtomicStack::new();
+        s.push(1); s.push(2); s.push(3);
+        println!("len: {}, empty: {}", s.len(), s.is_empty());
+        println!("pop: {:?}, pop: {:?}", s.pop(), s.pop());
+        println!("len: {}, empty: {}", s.len(), s.is_empty());
+        println!("================================================");
+    }
+
+    fn buffer_pool_ops() {
+        println!("SHARED BUFFER POOL");
+        let pool = Arc::new(AtomicStack::new());
+        for _ in 0..4 {
+            pool.push(vec![0u8; 1024]);
+        }
+        let workers: Vec<_> = (0..4).map(|id| {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    // Take a free buffer, or allocate if the pool is dry.
+                    let mut buf = pool.pop().unwrap_or_else(|| vec![0u8; 1024]);
+                    buf[0] = id;
+                    pool.push(buf);
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        println!("Buffers in the pool: {}", pool.len());
+        println!("================================================");
+    }
+
+    fn stress_ops() {
+        println!("STRESS TEST");
+        // Every thread pushes its own range and pops as much as it can.
+        // Each value must come out exactly once.
+        let threads = 8;
+        let per_thread = 50_000;
+        let s = AtomicStack::new();
+        let popped: Vec<Vec<usize>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|t| {
+                let s = &s;
+                scope.spawn(move || {
+                    let mut got = Vec::new();
+                    for i in 0..per_thread {
+                        s.push(t * per_thread + i);
+                        if i % 3 != 0 {
+                            if let Some(v) = s.pop() {
+                                got.push(v);
+                            }
+                        }
+                    }
+                    got
+                })
+            }).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut seen: Vec<usize> = popped.into_iter().flatten().collect();
+        while let Some(v) = s.pop() {
+            seen.push(v);
+        }
+        seen.sort();
+        let expected: Vec<usize> = (0..threads * per_thread).collect();
+        assert_eq!(seen, expected, "values lost or duplicated");
+        println!("{} threads, {} values, each popped exactly once", threads, seen.len());
+
+        // Values that own memory are dropped exactly once as well.
+        let counter = Arc::new(AtomicUsize::new(0));
+        struct Counted(Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) { self.0.fetch_add(1, Ordering::SeqCst); }
+        }
+        {
+            let s = AtomicStack::new();
+            thread::scope(|scope| {
+                for _ in 0..4 {
+                    let s = &s;
+                    let counter = &counter;
+                    scope.spawn(move || {
+                        for i in 0..10_000 {
+                            s.push(Counted(Arc::clone(counter)));
+                            if i % 2 == 0 { s.pop(); }
+                        }
+                    });
+                }
+            });
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 40_000);
+        println!("40000 values dropped exactly once");
+        println!("================================================");
+    }
+}
@@ -0,0 +1,252 @@
+//! Blocking queue.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// A thread-safe bounded FIFO queue for producer/consumer pipelines.
+/// It wraps the `Queue<T>` of rusqueue.rs in a Mutex, with two condition
+/// variables: producers wait on `not_full`, consumers on `not_empty`.
+/// After `close()` pushes fail and pops drain what is left, then return None.
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Queue<T> {
+    cap: usize, // Capacity
+    data: Vec<T>, // Storing elements
+}
+
+impl<T> Queue<T> {
+    fn new(size: usize) -> Self {
+        Self { cap: size, data: Vec::with_capacity(size) }
+    }
+
+    fn is_empty(&self) -> bool { Self::len(&self) == 0 }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+    fn len(&self) -> usize { self.data.len() }
+
+    // enqueue or add data to the queue
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        if self.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    // pop out values.
+    fn dequeue(&mut self) -> Option<T> {
+        if self.len() > 0 {
+            self.data.pop()
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a push failed. The rejected value is handed back.
+#[derive(Debug, PartialEq)]
+enum PushError<T> {
+    Full(T),
+    Timeout(T),
+    Closed(T),
+}
+
+#[derive(Debug)]
+struct State<T> {
+    queue: Queue<T>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct BlockingQueue<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+    not_empty: Condvar,
+}
+
+impl<T> BlockingQueue<T> {
+    // Same capacity semantics as `Queue::new(cap)`.
+    fn new(cap: usize) -> Self {
+        Self {
+            state: Mutex::new(State { queue: Queue::new(cap), closed: false }),
+            not_full: Condvar::new(),
+            not_empty: Condvar::new(),
+        }
+    }
+
+    // A panic in another thread does not make the queue unusable.
+    fn lock(&self) -> MutexGuard<State<T>> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn len(&self) -> usize { self.lock().queue.len() }
+    fn is_empty(&self) -> bool { self.lock().queue.is_empty() }
+    fn is_full(&self) -> bool { self.lock().queue.is_full() }
+    fn capacity(&self) -> usize { self.lock().queue.cap }
+    fn is_closed(&self) -> bool { self.lock().closed }
+
+    // Wake every waiting thread. Pushes fail from now on, pops drain the
+    // remaining items and then return None.
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_full.notify_all();
+        self.not_empty.notify_all();
+    }
+
+    // Add to the queue, blocking while it is full.
+    fn push(&self, val: T) -> Result<(), PushError<T>> {
+        let mut state = self.lock();
+        while state.queue.is_full() && !state.closed {
+            state = self.not_full.wait(state).unwrap_or_else(|err| err.into_inner());
+        }
+        self.enqueue_locked(state, val)
+    }
+
+    // Add to the queue if there is space right now.
+    fn try_push(&self, val: T) -> Result<(), PushError<T>> {
+        let state = self.lock();
+        if state.queue.is_full() && !state.closed {
+            return Err(PushError::Full(val));
+        }
+        self.enqueue_locked(state, val)
+    }
+
+    // Add to the queue, waiting at most `timeout` for space.
+    fn push_timeout(&self, val: T, timeout: Duration) -> Result<(), PushError<T>> {
+        // A deadline too far off to represent means no deadline.
+        let deadline = match Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return self.push(val),
+        };
+        let mut state = self.lock();
+        while state.queue.is_full() && !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PushError::Timeout(val));
+            }
+            state = self.not_full.wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner()).0;
+        }
+        self.enqueue_locked(state, val)
+    }
+
+    fn enqueue_locked(&self, mut state: MutexGuard<State<T>>, val: T) -> Result<(), PushError<T>> {
+        if state.closed {
+            return Err(PushError::Closed(val));
+        }
+        // Space was checked under the same lock, so this cannot fail.
+        let _ = state.queue.enqueue(val);
+        drop(state);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    // Take from the queue, blocking while it is empty.
+    // None once the queue is closed and drained.
+    fn pop(&self) -> Option<T> {
+        let mut state = self.lock();
+        while state.queue.is_empty() && !state.closed {
+            state = self.not_empty.wait(state).unwrap_or_else(|err| err.into_inner());
+        }
+        self.dequeue_locked(state)
+    }
+
+    // Take from the queue if there is an item right now.
+    fn try_pop(&self) -> Option<T> {
+        self.dequeue_locked(self.lock())
+    }
+
+    // Take from the queue, waiting at most `timeout` for an item.
+    fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        // A deadline too far off to represent means no deadline.
+        let deadline = match Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return self.pop(),
+        };
+        let mut state = self.lock();
+        while state.queue.is_empty() && !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            state = self.not_empty.wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner()).0;
+        }
+        self.dequeue_locked(state)
+    }
+
+    fn dequeue_locked(&self, mut state: MutexGuard<State<T>>) -> Option<T> {
+        let val = state.queue.dequeue();
+        drop(state);
+        if val.is_some() {
+            self.not_full.notify_one();
+        }
+        val
+    }
+}
+
+fn main() {
+    basic_ops();
+    timeout_ops();
+    pipeline_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let q = BlockingQueue::new(2);
+        q.push("Huxley").unwrap();
+        q.try_push("Adonijah").unwrap();
+        println!("try_push when full: {:?}", q.try_push("Harriet"));
+        println!("len: {}, full: {}", q.len(), q.is_full());
+        println!("try_pop: {:?}", q.try_pop());
+        q.close();
+        println!("push after close: {:?}", q.push("Merit"));
+        println!("pop after close: {:?}, then {:?}", q.pop(), q.pop());
+        println!("=========================");
+    }
+
+    fn timeout_ops() {
+        println!("TIMEOUTS");
+        let q = BlockingQueue::new(1);
+        let start = Instant::now();
+        println!("pop_timeout on empty: {:?}", q.pop_timeout(Duration::from_millis(50)));
+        q.push(1).unwrap();
+        println!("push_timeout on full: {:?}", q.push_timeout(2, Duration::from_millis(50)));
+        println!("waited about {} ms", start.elapsed().as_millis() / 10 * 10);
+        // Too long to add to `Instant::now()`: waits with no deadline.
+        println!("pop_timeout(Duration::MAX): {:?}", q.pop_timeout(Duration::MAX));
+        println!("push_timeout(Duration::MAX): {:?}", q.push_timeout(3, Duration::MAX));
+        println!("=========================");
+    }
+
+    fn pipeline_ops() {
+        println!("PRODUCER / CONSUMER PIPELINE");
+        // Three producers, two consumers, a queue of four slots.
+        let q = Arc::new(BlockingQueue::new(4));
+        let producers: Vec<_> = (0..3).map(|p| {
+            let q = Arc::clone(&q);
+            thread::spawn(move || {
+                for i in 0..1000 {
+                    q.push(p * 1000 + i).unwrap();
+                }
+            })
+        }).collect();
+        let consumers: Vec<_> = (0..2).map(|_| {
+            let q = Arc::clone(&q);
+            thread::spawn(move || {
+                let mut sum: u64 = 0;
+                while let Some(val) = q.pop() {
+                    sum += val as u64;
+                }
+                sum
+            })
+        }).collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        // Consumers finish once everything is drained.
+        q.close();
+        let total: u64 = consumers.into_iter().map(|c| c.join().unwrap()).sum();
+        let expected: u64 = (0..3000).sum();
+        println!("consumed sum: {}, expected: {}", total, expected);
+        println!("=========================");
+    }
+}
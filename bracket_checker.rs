@@ -0,0 +1,587 @@
+//! BRACKET CHECKER.
+#![allow(warnings)]
+/// A generalised version of `par_checker` from expression_calc.rs.
+/// The bracket pairs are configurable and can be single characters like `<>`
+/// or keywords like `begin`/`end`. Text inside quotes and comments is skipped.
+/// On failure a diagnostic says where the problem is and which closer
+/// was expected, instead of a bare `false`.
+/// `StreamChecker` does the same for input fed in chunks.
+///
+/// Run with file names as arguments to lint them, or without for a demo.
+/// `--stream` before the file names checks them as JSON-like data in chunks.
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::process;
+
+#[derive(Debug)]
+struct Stack<T> {
+    size: usize,
+    data: Vec<T>,
+}
+
+impl<T> Stack<T> {
+    fn new() -> Self {
+        Self { size: 0, data: Vec::new() }
+    }
+
+    fn is_empty(&self) -> bool { self.size == 0 }
+    fn len(&self) -> usize { self.size }
+
+    fn clear(&mut self) {
+        self.size = 0;
+        self.data.clear();
+    }
+
+    fn push(&mut self, val: T) {
+        self.data.push(val);
+        self.size += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.size == 0 {
+            return None;
+        }
+        self.size -= 1;
+        self.data.pop()
+    }
+
+    fn peek(&self) -> Option<&T> {
+        if self.size == 0 {
+            return None;
+        }
+        self.data.get(self.size - 1)
+    }
+}
+
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Lifo<T>: Container {
+    fn push(&mut self, val: T) -> Result<(), String>;
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<&T>;
+}
+
+impl<T> Container for Stack<T> {
+    fn len(&self) -> usize { Stack::len(self) }
+    fn clear(&mut self) { Stack::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Lifo<T> for Stack<T> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        Stack::push(self, val);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> { Stack::pop(self) }
+    fn peek(&self) -> Option<&T> { Stack::peek(self) }
+}
+
+/// What went wrong while checking.
+#[derive(Debug, Clone, PartialEq)]
+enum Problem {
+    // An opener that was never closed.
+    Unclosed { opener: String, expected: String },
+    // A closer with no opener before it.
+    Stray { closer: String },
+    // A closer that does not match the innermost opener.
+    Mismatched { found: String, expected: String, opener_pos: usize },
+    // A quote or block comment running to the end of the input.
+    Unterminated { opener: String, expected: String },
+}
+
+/// Diagnostic for the first problem found.
+/// `pos` is a byte offset, `line` and `col` count from 1.
+#[derive(Debug, Clone, PartialEq)]
+struct Diagnostic {
+    pos: usize,
+    line: usize,
+    col: usize,
+    problem: Problem,
+}
+
+impl Diagnostic {
+    fn new(text: &str, pos: usize, problem: Problem) -> Self {
+        let (line, col) = line_col(text, pos);
+        Self { pos, line, col, problem }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.col)?;
+        match &self.problem {
+            Problem::Unclosed { opener, expected } => {
+                write!(f, "'{}' is never closed, expected '{}'", opener, expected)
+            }
+            Problem::Stray { closer } => {
+                write!(f, "'{}' has no matching opener", closer)
+            }
+            Problem::Mismatched { found, expected, opener_pos } => {
+                write!(f, "found '{}' but expected '{}' (opened at byte {})",
+                       found, expected, opener_pos)
+            }
+            Problem::Unterminated { opener, expected } => {
+                write!(f, "'{}' is never terminated, expected '{}'", opener, expected)
+            }
+        }
+    }
+}
+
+/// Line and column (both from 1, column in chars) of byte offset `pos`.
+fn line_col(text: &str, pos: usize) -> (usize, usize) {
+    let before = &text[..pos];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+// Keywords such as `begin` only count as brackets when they stand alone,
+// so `beginning` or `send` are left alone.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn token_at(text: &str, pos: usize, token: &str) -> bool {
+    if !text[pos..].starts_with(token) {
+        return false;
+    }
+    let first = token.chars().next().unwrap();
+    let last = token.chars().last().unwrap();
+    if is_word_char(first) && text[..pos].chars().last().map_or(false, is_word_char) {
+        return false;
+    }
+    if is_word_char(last) && text[pos + token.len()..].chars().next().map_or(false, is_word_char) {
+        return false;
+    }
+    true
+}
+
+/// Bracket checker with configurable pairs, quotes and comments.
+#[derive(Debug, Clone)]
+struct BracketChecker {
+    pairs: Vec<(String, String)>,
+    // Text between a quote pair is not checked. `\` escapes the next char.
+    quotes: Vec<(String, String)>,
+    // Line comments run to the end of the line.
+    line_comments: Vec<String>,
+    block_comments: Vec<(String, String)>,
+}
+
+impl BracketChecker {
+    // Checker with no pairs at all.
+    fn empty() -> Self {
+        Self {
+            pairs: Vec::new(),
+            quotes: Vec::new(),
+            line_comments: Vec::new(),
+            block_comments: Vec::new(),
+        }
+    }
+
+    // The same brackets as `par_checker`: "({[" and ")}]".
+    fn new() -> Self {
+        Self::empty().pair("(", ")").pair("[", "]").pair("{", "}")
+    }
+
+    fn pair(mut self, open: &str, close: &str) -> Self {
+        self.pairs.push((open.to_string(), close.to_string()));
+        self
+    }
+
+    fn quote(mut self, open: &str, close: &str) -> Self {
+        self.quotes.push((open.to_string(), close.to_string()));
+        self
+    }
+
+    fn line_comment(mut self, start: &str) -> Self {
+        self.line_comments.push(start.to_string());
+        self
+    }
+
+    fn block_comment(mut self, open: &str, close: &str) -> Self {
+        self.block_comments.push((open.to_string(), close.to_string()));
+        self
+    }
+
+    fn is_balanced(&self, text: &str) -> bool {
+        self.check(text).is_ok()
+    }
+
+    // Check the text, returning the first problem found.
+    fn check(&self, text: &str) -> Result<(), Diagnostic> {
+        // Open brackets as (index into pairs, position).
+        let mut stack: Stack<(usize, usize)> = Stack::new();
+        // Longer tokens are tried first so `{{` is not taken for `{`.
+        let mut order: Vec<usize> = (0..self.pairs.len()).collect();
+        order.sort_by_key(|&index| Reverse(self.pairs[index].0.len()));
+        let mut pos = 0;
+        'scan: while pos < text.len() {
+            // Comments and quotes are skipped as a whole.
+            for start in self.line_comments.iter() {
+                if text[pos..].starts_with(start.as_str()) {
+                    pos = match text[pos..].find('\n') {
+                        Some(end) => pos + end,
+                        None => text.len(),
+                    };
+                    continue 'scan;
+                }
+            }
+            for (open, close) in self.block_comments.iter() {
+                if text[pos..].starts_with(open.as_str()) {
+                    let body = pos + open.len();
+                    match text[body..].find(close.as_str()) {
+                        Some(end) => pos = body + end + close.len(),
+                        None => return Err(Diagnostic::new(text, pos, Problem::Unterminated {
+                            opener: open.clone(), expected: close.clone(),
+                        })),
+                    }
+                    continue 'scan;
+                }
+            }
+            for (open, close) in self.quotes.iter() {
+                if text[pos..].starts_with(open.as_str()) {
+                    pos = self.skip_quote(text, pos, open, close)?;
+                    continue 'scan;
+                }
+            }
+
+            // A closer of the innermost open pair wins over everything else,
+            // which lets pairs like `|`/`|` use the same token on both sides.
+            if let Some(&(top, _)) = stack.peek() {
+                let close = &self.pairs[top].1;
+                if token_at(text, pos, close) {
+                    stack.pop();
+                    pos += close.len();
+                    continue 'scan;
+                }
+            }
+            for &index in order.iter() {
+                let open = &self.pairs[index].0;
+                if token_at(text, pos, open) {
+                    stack.push((index, pos));
+                    pos += open.len();
+                    continue 'scan;
+                }
+            }
+            for &index in order.iter() {
+                let close = &self.pairs[index].1;
+                if token_at(text, pos, close) {
+                    let problem = match stack.peek() {
+                        Some(&(top, top_pos)) => Problem::Mismatched {
+                            found: close.clone(),
+                            expected: self.pairs[top].1.clone(),
+                            opener_pos: top_pos,
+                        },
+                        None => Problem::Stray { closer: close.clone() },
+                    };
+                    return Err(Diagnostic::new(text, pos, problem));
+                }
+            }
+            pos += text[pos..].chars().next().unwrap().len_utf8();
+        }
+
+        match stack.pop() {
+            // Report the innermost opener left, it is the one that should
+            // have been closed first.
+            Some((index, open_pos)) => {
+                let (open, close) = &self.pairs[index];
+                Err(Diagnostic::new(text, open_pos, Problem::Unclosed {
+                    opener: open.clone(), expected: close.clone(),
+                }))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Position just after the quote starting at `pos`.
+    fn skip_quote(&self, text: &str, pos: usize, open: &str, close: &str) -> Result<usize, Diagnostic> {
+        let mut index = pos + open.len();
+        while index < text.len() {
+            if text[index..].starts_with('\\') {
+                index += 1;
+                if index < text.len() {
+                    index += text[index..].chars().next().unwrap().len_utf8();
+                }
+            } else if text[index..].starts_with(close) {
+                return Ok(index + close.len());
+            } else {
+                index += text[index..].chars().next().unwrap().len_utf8();
+            }
+        }
+        Err(Diagnostic::new(text, pos, Problem::Unterminated {
+            opener: open.to_string(), expected: close.to_string(),
+        }))
+    }
+}
+
+/// STREAMING CHECKER.
+/// Checks input fed in chunks, so large files can be validated through a
+/// `Read` without loading them. Only the stack of open brackets is kept.
+/// Brackets and quotes are single bytes, which covers JSON-like data; a
+/// chunk may end anywhere, even inside a multi-byte character.
+#[derive(Debug)]
+struct StreamChecker {
+    pairs: Vec<(u8, u8)>,
+    quotes: Vec<u8>,
+    // Open brackets as (opener, offset, line, col).
+    stack: Stack<(u8, usize, usize, usize)>,
+    // Position of the next byte.
+    offset: usize,
+    line: usize,
+    col: usize,
+    // Open quote as (quote, offset, line, col) and whether `\` came last.
+    quote: Option<(u8, usize, usize, usize)>,
+    escaped: bool,
+    // The first problem found, checking stops there.
+    error: Option<Diagnostic>,
+}
+
+impl StreamChecker {
+    // "()", "[]" and "{}" with `"` strings, as in JSON.
+    fn new() -> Self {
+        Self::with_pairs(&[(b'(', b')'), (b'[', b']'), (b'{', b'}')], &[b'"'])
+    }
+
+    fn with_pairs(pairs: &[(u8, u8)], quotes: &[u8]) -> Self {
+        Self {
+            pairs: pairs.to_vec(),
+            quotes: quotes.to_vec(),
+            stack: Stack::new(),
+            offset: 0,
+            line: 1,
+            col: 1,
+            quote: None,
+            escaped: false,
+            error: None,
+        }
+    }
+
+    // Depth of open brackets so far.
+    fn depth(&self) -> usize { self.stack.len() }
+
+    // Check the next chunk of input, `&str` and `&[u8]` both work.
+    fn feed<B: AsRef<[u8]>>(&mut self, chunk: B) -> Result<(), Diagnostic> {
+        if let Some(diag) = &self.error {
+            return Err(diag.clone());
+        }
+        for &b in chunk.as_ref() {
+            if let Err(diag) = self.byte(b) {
+                self.error = Some(diag.clone());
+                return Err(diag);
+            }
+            self.offset += 1;
+            if b == b'\n' {
+                self.line += 1;
+                self.col = 1;
+            } else if b & 0xC0 != 0x80 {
+                // UTF-8 continuation bytes do not start a new column.
+                self.col += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn byte(&mut self, b: u8) -> Result<(), Diagnostic> {
+        if let Some((quote, ..)) = self.quote {
+            if self.escaped {
+                self.escaped = false;
+            } else if b == b'\\' {
+                self.escaped = true;
+            } else if b == quote {
+                self.quote = None;
+            }
+            return Ok(());
+        }
+        if self.quotes.contains(&b) {
+            self.quote = Some((b, self.offset, self.line, self.col));
+            return Ok(());
+        }
+        if let Some(&(open, ..)) = self.stack.peek() {
+            if self.closer_of(open) == Some(b) {
+                self.stack.pop();
+                return Ok(());
+            }
+        }
+        if self.closer_of(b).is_some() {
+            self.stack.push((b, self.offset, self.line, self.col));
+            return Ok(());
+        }
+        if self.pairs.iter().any(|&(_, close)| close == b) {
+            let closer = char::from(b).to_string();
+            let problem = match self.stack.peek() {
+                Some(&(open, open_pos, ..)) => Problem::Mismatched {
+                    found: closer,
+                    expected: char::from(self.closer_of(open).unwrap()).to_string(),
+                    opener_pos: open_pos,
+                },
+                None => Problem::Stray { closer },
+            };
+            return Err(self.diagnostic(self.offset, self.line, self.col, problem));
+        }
+        Ok(())
+    }
+
+    fn closer_of(&self, open: u8) -> Option<u8> {
+        self.pairs.iter().find(|&&(o, _)| o == open).map(|&(_, close)| close)
+    }
+
+    fn diagnostic(&self, pos: usize, line: usize, col: usize, problem: Problem) -> Diagnostic {
+        Diagnostic { pos, line, col, problem }
+    }
+
+    // Feed everything `reader` has, stopping early at the first problem.
+    fn read_from<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if self.feed(&buf[..n]).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    // End of input: anything still open is an error.
+    fn finish(self) -> Result<(), Diagnostic> {
+        if let Some(diag) = self.error {
+            return Err(diag);
+        }
+        if let Some((quote, pos, line, col)) = self.quote {
+            let quote = char::from(quote).to_string();
+            return Err(self.diagnostic(pos, line, col, Problem::Unterminated {
+                opener: quote.clone(), expected: quote,
+            }));
+        }
+        match self.stack.peek() {
+            Some(&(open, pos, line, col)) => {
+                Err(self.diagnostic(pos, line, col, Problem::Unclosed {
+                    opener: char::from(open).to_string(),
+                    expected: char::from(self.closer_of(open).unwrap()).to_string(),
+                }))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// Checker used for config templates: brackets, `{{ }}` and `{% %}` tags,
+// `begin`/`end` blocks, double quotes and comments. Single quotes are left
+// out, as apostrophes (`# don't`, `it's`) are common in plain config text;
+// add `.quote("'", "'")` for formats that quote with them.
+fn template_checker() -> BracketChecker {
+    BracketChecker::new()
+        .pair("<", ">")
+        .pair("begin", "end")
+        .pair("{{", "}}")
+        .pair("{%", "%}")
+        .quote("\"", "\"")
+        .line_comment("#")
+        .block_comment("{#", "#}")
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let stream = args.first().map_or(false, |arg| arg == "--stream");
+    if stream {
+        args.remove(0);
+    }
+    if args.is_empty() {
+        default_ops();
+        config_ops();
+        stream_ops();
+        return;
+    }
+
+    // Lint every file given, exit with 1 if any of them fails.
+    // With --stream files are read in chunks with the JSON-like checker.
+    let checker = template_checker();
+    let mut ok = true;
+    for path in args {
+        let result = if stream {
+            File::open(&path).and_then(|file| {
+                let mut checker = StreamChecker::new();
+                checker.read_from(file)?;
+                Ok(checker.finish())
+            })
+        } else {
+            fs::read_to_string(&path).map(|text| checker.check(&text))
+        };
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(diag)) => {
+                println!("{}:{}", path, diag);
+                ok = false;
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                ok = false;
+            }
+        }
+    }
+    if !ok {
+        process::exit(1);
+    }
+
+    fn default_ops() {
+        println!("DEFAULT BRACKETS");
+        let checker = BracketChecker::new();
+        for text in ["(a[b]{c})", "(a]", "a)", "((a)", "f(\"(\")"] {
+            match checker.check(text) {
+                Ok(()) => println!("{:12} balanced", text),
+                Err(diag) => println!("{:12} {}", text, diag),
+            }
+        }
+        println!("=======================================");
+    }
+
+    fn config_ops() {
+        println!("CONFIG TEMPLATES");
+        let checker = template_checker();
+        let good = "server <name=\"a)b\"> {\n  {% if tls %}port = {{ port }}{% endif %}\n  # stray ) in a comment, don't mind it\n  begin listen(80) end\n}\n";
+        let bad = "server {\n  begin\n    listen [80)\n  end\n}\n";
+        let open = "begin\n  path = '/tmp/{x}'\n";
+        for text in [good, bad, open] {
+            match checker.check(text) {
+                Ok(()) => println!("balanced"),
+                Err(diag) => println!("{}", diag),
+            }
+        }
+        println!("=======================================");
+    }
+
+    fn stream_ops() {
+        println!("STREAMING CHECKER");
+        // The same log fed in small chunks, split inside strings and brackets.
+        let log = "{\"msg\": \"a ] in a string\", \"tags\": [1, 2]}\n{\"user\": \"é\", \"ids\": [3, 4}\n";
+        let mut checker = StreamChecker::new();
+        for chunk in log.as_bytes().chunks(5) {
+            if checker.feed(chunk).is_err() {
+                break;
+            }
+        }
+        match checker.finish() {
+            Ok(()) => println!("balanced"),
+            Err(diag) => println!("{} (byte {})", diag, diag.pos),
+        }
+
+        let mut checker = StreamChecker::new();
+        checker.read_from("[{\"a\": [1, 2]}, {\"b\": \"}\"}]".as_bytes()).unwrap();
+        println!("read_from: {:?}", checker.finish());
+        println!("=======================================");
+    }
+}
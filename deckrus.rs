@@ -1,7 +1,19 @@
 //! Deque  
+#![allow(warnings)]
 /// Allows items to be added and removed from both sides.
 /// Behaves both as a stack and a queue.
-#![allow(warnings)]
+///
+/// The two ends are the front and the back: `push_front`/`pop_front`
+/// work on one, `push_back`/`pop_back` on the other, and `front`/`back`
+/// peek at them. Iteration goes from the front to the back, and positions
+/// follow the same order: 0 is the front, len - 1 the back.
+///
+/// `data` holds the items in that order, so the back end is the cheap one:
+/// `push_back` and `pop_back` are O(1), the front shifts every item.
+///
+/// The old names `add_front`, `add_rear`, `remove_front`, `remove_rear` and
+/// `extend_rear` still work. The rear is the back.
+use std::ops::{Bound, Deref, DerefMut, Index, IndexMut, RangeBounds};
 
 #[derive(Debug)]
 struct Deque<T> {
@@ -26,43 +38,238 @@ impl<T> Deque<T> {
     }
 
     // Adding data at the front or start of the deque
-    fn add_front(&mut self, val: T) -> Result<(), String> {
+    fn push_front(&mut self, val: T) -> Result<(), String> {
         if self.is_full() {
             return Err("No space available".to_string());
         }
-        self.data.push(val);
+        self.data.insert(0, val);
         Ok(())
     }
 
-    // Adding data at the rear or end of the deque
-    fn add_rear(&mut self, val: T) -> Result<(), String> {
+    // Adding data at the back or end of the deque
+    fn push_back(&mut self, val: T) -> Result<(), String> {
         if self.is_full() {
             return Err("No space available".to_string());
         }
-        self.data.insert(0, val);
+        self.data.push(val);
         Ok(())
     }
 
     // Remove data from the start or front of the deque
-    fn remove_front(&mut self) -> Option<T> {
+    fn pop_front(&mut self) -> Option<T> {
         if self.len() > 0 {
-            self.data.pop()
+            Some(self.data.remove(0))
         } else {
             None
         }
     }
 
-    // Remove data from the rear or end of the deque
+    // Remove data from the back or end of the deque
+    fn pop_back(&mut self) -> Option<T> {
+        self.data.pop()
+    }
+
+    // The item at the front, the next `pop_front`.
+    fn front(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    // The item at the back, the next `pop_back`.
+    fn back(&self) -> Option<&T> {
+        self.data.last()
+    }
+
+    // PEEKING.
+    //
+    // The rear is the back. The `_mut` versions give a `PeekMut` guard: edit
+    // the item through it, or take it off with `PeekMut::pop` once it has
+    // been looked at.
+
+    fn peek_front(&self) -> Option<&T> { self.front() }
+    fn peek_rear(&self) -> Option<&T> { self.back() }
+
+    fn peek_front_mut(&mut self) -> Option<PeekMut<T>> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(PeekMut { deque: self, front: true })
+    }
+
+    fn peek_rear_mut(&mut self) -> Option<PeekMut<T>> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(PeekMut { deque: self, front: false })
+    }
+
+    // OLD NAMES.
+    //
+    // Kept so existing callers keep working, with the same ends as before.
+
+    #[deprecated(note = "use push_front")]
+    fn add_front(&mut self, val: T) -> Result<(), String> {
+        self.push_front(val)
+    }
+
+    #[deprecated(note = "use push_back")]
+    fn add_rear(&mut self, val: T) -> Result<(), String> {
+        self.push_back(val)
+    }
+
+    #[deprecated(note = "use pop_front")]
+    fn remove_front(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    #[deprecated(note = "use pop_back")]
     fn remove_rear(&mut self) -> Option<T> {
-        if self.len() > 0 {
-            Some(self.data.remove(0))
-        } else {
-            None
+        self.pop_back()
+    }
+
+    #[deprecated(note = "use extend_back")]
+    fn extend_rear<I: IntoIterator<Item = T>>(&mut self, items: I) -> Result<(), String> {
+        self.extend_back(items)
+    }
+
+    // RANDOM ACCESS.
+
+    // The item at position `i`, counted from the front.
+    fn get(&self, i: usize) -> Option<&T> {
+        self.data.get(i)
+    }
+
+    fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        self.data.get_mut(i)
+    }
+
+    // Swap the items at two positions. Panics if either is out of bounds.
+    fn swap(&mut self, i: usize, j: usize) {
+        self.data.swap(i, j);
+    }
+
+    // Move the first `n` positions to the end: the `n` items nearest the
+    // front go to the back. Panics if `n` is larger than the length.
+    fn rotate_left(&mut self, n: usize) {
+        self.data.rotate_left(n);
+    }
+
+    // Move the last `n` positions to the start: the `n` items nearest the
+    // back go to the front. Panics if `n` is larger than the length.
+    fn rotate_right(&mut self, n: usize) {
+        self.data.rotate_right(n);
+    }
+
+    // The contents as two slices in position order. The storage is a single
+    // Vec, so the second slice is always empty.
+    fn as_slices(&self) -> (&[T], &[T]) {
+        (&self.data[..], &[])
+    }
+
+    // The contents as one mutable slice in position order.
+    fn make_contiguous(&mut self) -> &mut [T] {
+        &mut self.data[..]
+    }
+
+    // Iterate over the positions in `range`, e.g. `deck.range(1..3)`.
+    // Panics if the range is out of bounds.
+    fn range<R: RangeBounds<usize>>(&self, range: R) -> Iter<T> {
+        let (start, end) = self.bounds(range);
+        let mut iterator = Iter { deque: Vec::new() };
+        for item in self.data[start..end].iter() {
+            iterator.deque.push(item);
+        }
+        iterator
+    }
+
+    fn range_mut<R: RangeBounds<usize>>(&mut self, range: R) -> IterMut<T> {
+        let (start, end) = self.bounds(range);
+        let mut iterator = IterMut { deque: Vec::new() };
+        for item in self.data[start..end].iter_mut() {
+            iterator.deque.push(item);
         }
+        iterator
+    }
+
+    fn bounds<R: RangeBounds<usize>>(&self, range: R) -> (usize, usize) {
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(start <= end && end <= self.len(), "range {start}..{end} out of bounds for deque of length {}", self.len());
+        (start, end)
+    }
+
+    // BULK OPERATIONS.
+    //
+    // Operations adding items fail as a whole, leaving everything unchanged,
+    // when the items would not fit in `cap`.
+
+    fn check_space(&self, n: usize) -> Result<(), String> {
+        let free = self.cap - self.len();
+        if n > free {
+            return Err(format!("No space available for {n} items, {free} free"));
+        }
+        Ok(())
+    }
+
+    // Move every item of `other` behind the back, keeping their order: the
+    // front of `other` ends up right behind the old back.
+    fn append(&mut self, other: &mut Deque<T>) -> Result<(), String> {
+        self.check_space(other.len())?;
+        self.data.append(&mut other.data);
+        Ok(())
+    }
+
+    // Split into positions ..at, kept, and at.., returned as a new deque with
+    // the same capacity. Panics if `at` is larger than the length.
+    fn split_off(&mut self, at: usize) -> Deque<T> {
+        let mut other = Deque::new(self.cap);
+        other.data.extend(self.data.drain(at..));
+        other
+    }
+
+    // Remove the positions in `range`, handing them out in position order.
+    fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<T> {
+        let (start, end) = self.bounds(range);
+        Drain(self.data.drain(start..end).collect::<Vec<_>>().into_iter())
+    }
+
+    // Keep only the items for which `pred` is true.
+    fn retain<F: FnMut(&T) -> bool>(&mut self, pred: F) {
+        self.data.retain(pred);
+    }
+
+    // Keep positions ..n, dropping the items nearest the back.
+    fn truncate(&mut self, n: usize) {
+        self.data.truncate(n);
+    }
+
+    // `push_front` every item in turn: the last one becomes the front.
+    fn extend_front<I: IntoIterator<Item = T>>(&mut self, items: I) -> Result<(), String> {
+        let items: Vec<T> = items.into_iter().collect();
+        self.check_space(items.len())?;
+        self.data.splice(0..0, items.into_iter().rev());
+        Ok(())
+    }
+
+    // `push_back` every item in turn: the last one becomes the back.
+    fn extend_back<I: IntoIterator<Item = T>>(&mut self, items: I) -> Result<(), String> {
+        let items: Vec<T> = items.into_iter().collect();
+        self.check_space(items.len())?;
+        self.data.extend(items);
+        Ok(())
     }
 
     // IMPLEMENTING ITERATION.
     //
+    // All three go from the front to the back.
+    //
     // IntoIter: Iterator modified and iterator is returned.
     fn into_iter(self) -> IntoIter<T> {
         IntoIter(self)
@@ -87,17 +294,63 @@ impl<T> Deque<T> {
     }
 }
 
+impl<T> Index<usize> for Deque<T> {
+    type Output = T;
+    fn index(&self, i: usize) -> &T {
+        &self.data[i]
+    }
+}
+
+impl<T> IndexMut<usize> for Deque<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        &mut self.data[i]
+    }
+}
+
+// PeekMut: the item at one end of a non-empty deque.
+struct PeekMut<'a, T: 'a> {
+    deque: &'a mut Deque<T>,
+    front: bool,
+}
+
+impl<'a, T> PeekMut<'a, T> {
+    // Remove the peeked item from its end and return it.
+    fn pop(this: PeekMut<'a, T>) -> T {
+        let item = if this.front { this.deque.pop_front() } else { this.deque.pop_back() };
+        item.unwrap()
+    }
+}
+
+impl<'a, T> Deref for PeekMut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        let item = if self.front { self.deque.data.first() } else { self.deque.data.last() };
+        item.unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PeekMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        let item = if self.front { self.deque.data.first_mut() } else { self.deque.data.last_mut() };
+        item.unwrap()
+    }
+}
 
 // IntoIter
 struct IntoIter<T>(Deque<T>);
 impl<T: Clone> Iterator for IntoIter<T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.0.is_empty() {
-            Some(self.0.data.remove(0))
-        } else {
-            None
-        }
+        self.0.pop_front()
+    }
+}
+
+// Drain
+struct Drain<T>(std::vec::IntoIter<T>);
+impl<T> Iterator for Drain<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
     }
 }
 
@@ -127,29 +380,86 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait DoubleEnded<T>: Container {
+    fn push_front(&mut self, val: T) -> Result<(), String>;
+    fn push_back(&mut self, val: T) -> Result<(), String>;
+    fn pop_front(&mut self) -> Option<T>;
+    fn pop_back(&mut self) -> Option<T>;
+}
+
+impl<T> Container for Deque<T> {
+    fn len(&self) -> usize { Deque::len(self) }
+    fn clear(&mut self) { Deque::clear(self) }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+impl<T> DoubleEnded<T> for Deque<T> {
+    fn push_front(&mut self, val: T) -> Result<(), String> { Deque::push_front(self, val) }
+    fn push_back(&mut self, val: T) -> Result<(), String> { Deque::push_back(self, val) }
+    fn pop_front(&mut self) -> Option<T> { Deque::pop_front(self) }
+    fn pop_back(&mut self) -> Option<T> { Deque::pop_back(self) }
+}
+
+// Move `n` items from the front round to the back, on any deque.
+fn rotate<T, D: DoubleEnded<T>>(deque: &mut D, n: usize) {
+    for _ in 0..n {
+        if let Some(item) = deque.pop_front() {
+            let _ = deque.push_back(item);
+        }
+    }
+}
+
 fn main() {
     basic_ops();
+    old_names_ops();
     iterative_ops();
+    random_access_ops();
+    bulk_ops();
+    peek_ops();
+    trait_ops();
 
     fn basic_ops() {
         println!("BASIC OPERATIONS");
         let mut deck = Deque::new(4);
-        deck.add_front("Galileo"); deck.add_rear("Galilei");
-        deck.add_front("Leonhard"); deck.add_rear("Euler");
+        deck.push_front("Galileo"); deck.push_back("Galilei");
+        deck.push_front("Leonhard"); deck.push_back("Euler");
         println!("The deque is {:?}", deck);
+        println!("Front: {:?}, Back: {:?}", deck.front(), deck.back());
         println!("Empty: {}, Full: {}, Len: {}", deck.is_empty(), deck.is_full(), deck.len());
-        deck.remove_front(); deck.remove_rear();
+        deck.pop_front(); deck.pop_back();
         println!("The deque is {:?}", deck);
         deck.clear();
         println!("Empty: {}, Full: {}, Len: {}", deck.is_empty(), deck.is_full(), deck.len());
         println!("================================================");
     }
 
+    // The only caller of the deprecated aliases, to show they still map to
+    // the same ends. The rear is the back.
+    #[allow(deprecated)]
+    fn old_names_ops() {
+        println!("DEPRECATED ALIASES");
+        let mut deck = Deque::new(4);
+        deck.add_front(2); deck.add_rear(3);
+        deck.add_front(1); deck.extend_rear(vec![4]).unwrap();
+        println!("front to back: {:?}", deck.iter().collect::<Vec<_>>());
+        println!("remove_front: {:?}, remove_rear: {:?}", deck.remove_front(), deck.remove_rear());
+        println!("================================================");
+    }
+
     fn iterative_ops() {
         println!("ITERATIVE OPERATIONS");
         let mut deck = Deque::new(4);
-        deck.add_front("Joseph"); deck.add_rear("Fourier");
-        deck.add_front("Simon"); deck.add_rear("Laplace");
+        deck.push_front("Joseph"); deck.push_back("Fourier");
+        deck.push_front("Simon"); deck.push_back("Laplace");
         println!("The immutable iterator is: \n");
         for item in deck.iter() {
             print!("{}, ",item);
@@ -160,4 +470,86 @@ fn main() {
         }
         println!("\n================================================");
     }
+
+    fn random_access_ops() {
+        println!("RANDOM ACCESS OPERATIONS");
+        // A parser buffering tokens, looking at them without draining.
+        let mut deck = Deque::new(6);
+        for token in ["let", "x", "=", "4", ";"] {
+            deck.push_back(token);
+        }
+        println!("front: {:?}, position 2: {}, past the end: {:?}", deck.get(0), deck[2], deck.get(9));
+        deck[3] = "42";
+        if let Some(token) = deck.get_mut(1) {
+            *token = "y";
+        }
+        println!("edited: {:?}", deck.as_slices());
+        println!("range 1..4: {:?}", deck.range(1..4).collect::<Vec<_>>());
+        deck.rotate_left(2);
+        println!("rotate_left(2): {:?}", deck.make_contiguous());
+        deck.rotate_right(2);
+        deck.swap(0, 4);
+        println!("rotate_right(2), swap(0, 4): {:?}", deck.range(..).collect::<Vec<_>>());
+        for token in deck.range_mut(3..) {
+            *token = "_";
+        }
+        println!("range_mut(3..) blanked: {:?}", deck.as_slices().0);
+        println!("================================================");
+    }
+
+    fn bulk_ops() {
+        println!("BULK OPERATIONS");
+        let mut deck = Deque::new(8);
+        deck.extend_back(vec![3, 4, 5]).unwrap();
+        deck.extend_front(vec![2, 1]).unwrap();
+        println!("extended: {:?}", deck.as_slices().0);
+        let mut other = Deque::new(4);
+        other.extend_back(vec![6, 7]).unwrap();
+        deck.append(&mut other).unwrap();
+        println!("appended behind the back: {:?}, other len {}", deck.as_slices().0, other.len());
+        println!("append over capacity: {:?}", deck.extend_back(vec![7, 8, 9]));
+        let back = deck.split_off(5);
+        println!("split_off(5): {:?} and {:?}", deck.as_slices().0, back.as_slices().0);
+        println!("drain(1..3): {:?}, left {:?}", deck.drain(1..3).collect::<Vec<_>>(), deck.as_slices().0);
+        deck.extend_back(vec![6, 7, 8, 9]).unwrap();
+        deck.retain(|&x| x % 2 == 0);
+        println!("even only: {:?}", deck.as_slices().0);
+        deck.truncate(2);
+        println!("truncate(2): {:?}", deck.as_slices().0);
+        println!("================================================");
+    }
+
+    fn peek_ops() {
+        println!("PEEK OPERATIONS");
+        let mut deck = Deque::new(4);
+        deck.extend_back(vec![("fetch", 0), ("parse", 2), ("render", 1)]).unwrap();
+        println!("front: {:?}, rear: {:?}", deck.peek_front(), deck.peek_rear());
+        // Count an attempt on the front item in place.
+        if let Some(mut job) = deck.peek_front_mut() {
+            job.1 += 1;
+        }
+        // Take the rear item only if it has been tried already.
+        if let Some(job) = deck.peek_rear_mut() {
+            if job.1 > 0 {
+                println!("popped the rear: {:?}", PeekMut::pop(job));
+            }
+        }
+        println!("left: {:?}", deck.as_slices().0);
+        let mut empty: Deque<i32> = Deque::new(1);
+        println!("peek on empty: {:?}", empty.peek_front());
+        println!("guard on empty: {}", empty.peek_rear_mut().is_none());
+        println!("================================================");
+    }
+
+    fn trait_ops() {
+        println!("DOUBLE ENDED TRAIT");
+        let mut deck = Deque::new(4);
+        for x in 1..=4 {
+            DoubleEnded::push_back(&mut deck, x).unwrap();
+        }
+        rotate(&mut deck, 1);
+        println!("front 1 rotated to the back: {:?}", deck.iter().collect::<Vec<_>>());
+        println!("len: {}, capacity: {:?}", Container::len(&deck), Container::capacity(&deck));
+        println!("================================================");
+    }
 }
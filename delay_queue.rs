@@ -0,0 +1,427 @@
+//! Delay queue.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// A queue where items only become available once their delay has passed,
+/// e.g. retries or session expiry. `insert` returns a `Key` to `reset` or
+/// `remove` the item later, `poll_expired(now)` hands out what is due.
+///
+/// Deadlines are kept in a hierarchical timing wheel with millisecond
+/// ticks: level 0 has 64 slots of 1 ms, level 1 has 64 slots of 64 ms, and
+/// so on for 6 levels (about two years). Inserting is O(1): an item goes in
+/// the slot of the highest level where its deadline differs from the
+/// current time. As time moves on, a slot of a higher level is emptied into
+/// the finer levels below it, and level 0 slots expire.
+///
+/// Time is a `Duration` since the start of a `Clock`. Tests use a
+/// `ManualClock` so nothing depends on the real time.
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Source of the current time.
+trait Clock {
+    fn now(&self) -> Duration;
+}
+
+/// Real time, measured from creation.
+struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Time that only moves when told to. Clones share the same time.
+#[derive(Clone)]
+struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    fn new() -> Self {
+        Self { now: Rc::new(Cell::new(Duration::ZERO)) }
+    }
+
+    fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+const LEVELS: usize = 6;
+const SLOT_BITS: u64 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+// One turn of the top level. Delays longer than this are pulled in to it.
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u64)) - 1;
+const NIL: usize = usize::MAX;
+
+/// Refers to an item in the queue. The generation makes keys of items that
+/// already expired or were removed stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    index: usize,
+    gen: u64,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    // None while the entry is free.
+    item: Option<T>,
+    gen: u64,
+    deadline: u64,
+    // Where the entry is linked: its wheel slot and neighbours there.
+    level: usize,
+    slot: usize,
+    prev: usize,
+    next: usize,
+}
+
+struct DelayQueue<T, C: Clock = SystemClock> {
+    clock: C,
+    // Tick up to which the wheel has been processed.
+    elapsed: u64,
+    entries: Vec<Entry<T>>,
+    free: Vec<usize>,
+    // First entry of every slot, and which slots are non-empty.
+    heads: [[usize; SLOTS]; LEVELS],
+    occupied: [u64; LEVELS],
+    size: usize,
+}
+
+impl<T> DelayQueue<T, SystemClock> {
+    fn new() -> Self {
+        Self::with_clock(SystemClock::new())
+    }
+}
+
+impl<T, C: Clock> DelayQueue<T, C> {
+    fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            elapsed: 0,
+            entries: Vec::new(),
+            free: Vec::new(),
+            heads: [[NIL; SLOTS]; LEVELS],
+            occupied: [0; LEVELS],
+            size: 0,
+        }
+    }
+
+    fn len(&self) -> usize { self.size }
+    fn is_empty(&self) -> bool { self.size == 0 }
+
+    // Add an item that becomes available after `delay`. O(1).
+    fn insert(&mut self, item: T, delay: Duration) -> Key {
+        let deadline = self.deadline_in(delay);
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.entries.push(Entry {
+                    item: None, gen: 0, deadline: 0, level: 0, slot: 0, prev: NIL, next: NIL,
+                });
+                self.entries.len() - 1
+            }
+        };
+        self.entries[index].item = Some(item);
+        self.entries[index].deadline = deadline;
+        self.link(index);
+        self.size += 1;
+        Key { index, gen: self.entries[index].gen }
+    }
+
+    // Move an item's deadline to `delay` from now. False for a stale key.
+    fn reset(&mut self, key: Key, delay: Duration) -> bool {
+        if !self.contains(key) {
+            return false;
+        }
+        self.unlink(key.index);
+        self.entries[key.index].deadline = self.deadline_in(delay);
+        self.link(key.index);
+        true
+    }
+
+    // Take an item out before it expires.
+    fn remove(&mut self, key: Key) -> Option<T> {
+        if !self.contains(key) {
+            return None;
+        }
+        self.unlink(key.index);
+        Some(self.release(key.index))
+    }
+
+    // Time left until an item expires, zero if it is already due.
+    fn remaining(&self, key: Key) -> Option<Duration> {
+        if !self.contains(key) {
+            return None;
+        }
+        let now = to_ticks(self.clock.now());
+        Some(Duration::from_millis(self.entries[key.index].deadline.saturating_sub(now)))
+    }
+
+    // Every item whose deadline is at or before `now`, earliest first.
+    fn poll_expired(&mut self, now: Duration) -> Vec<T> {
+        self.poll_expired_timed(now).into_iter().map(|(_, item)| item).collect()
+    }
+
+    // The same, each item with the deadline it expired at.
+    fn poll_expired_timed(&mut self, now: Duration) -> Vec<(Duration, T)> {
+        let now = to_ticks(now).max(self.elapsed);
+        let mut expired = Vec::new();
+        while let Some((level, slot, start)) = self.next_slot() {
+            if start > now {
+                break;
+            }
+            self.elapsed = self.elapsed.max(start);
+            // Take the whole slot: level 0 entries are due, the others are
+            // spread over the finer levels.
+            let mut cur = self.heads[level][slot];
+            self.heads[level][slot] = NIL;
+            self.occupied[level] &= !(1 << slot);
+            while cur != NIL {
+                let next = self.entries[cur].next;
+                if level == 0 {
+                    let deadline = Duration::from_millis(self.entries[cur].deadline);
+                    expired.push((deadline, self.release(cur)));
+                } else {
+                    self.link(cur);
+                }
+                cur = next;
+            }
+        }
+        self.elapsed = now;
+        expired
+    }
+
+    // Expired items according to the queue's own clock.
+    fn poll(&mut self) -> Vec<T> {
+        let now = self.clock.now();
+        self.poll_expired(now)
+    }
+
+    fn poll_timed(&mut self) -> Vec<(Duration, T)> {
+        let now = self.clock.now();
+        self.poll_expired_timed(now)
+    }
+
+    fn contains(&self, key: Key) -> bool {
+        match self.entries.get(key.index) {
+            Some(entry) => entry.gen == key.gen && entry.item.is_some(),
+            None => false,
+        }
+    }
+
+    fn deadline_in(&self, delay: Duration) -> u64 {
+        let now = to_ticks(self.clock.now()).max(self.elapsed);
+        now + to_ticks(delay).min(MAX_TICKS)
+    }
+
+    fn release(&mut self, index: usize) -> T {
+        let entry = &mut self.entries[index];
+        entry.gen += 1;
+        self.free.push(index);
+        self.size -= 1;
+        entry.item.take().unwrap()
+    }
+
+    // The slot for a deadline is picked by the highest 6-bit group where it
+    // differs from the current time. A deadline on the wheel's next turn
+    // differs above the top level and goes there, in a slot at or behind
+    // the current one.
+    fn link(&mut self, index: usize) {
+        let deadline = self.entries[index].deadline.max(self.elapsed);
+        let diff = (deadline ^ self.elapsed) | (SLOTS as u64 - 1);
+        let level = ((63 - diff.leading_zeros() as u64) / SLOT_BITS) as usize;
+        let level = level.min(LEVELS - 1);
+        let slot = ((deadline >> (SLOT_BITS * level as u64)) as usize) & (SLOTS - 1);
+        let head = self.heads[level][slot];
+        let entry = &mut self.entries[index];
+        entry.level = level;
+        entry.slot = slot;
+        entry.prev = NIL;
+        entry.next = head;
+        if head != NIL {
+            self.entries[head].prev = index;
+        }
+        self.heads[level][slot] = index;
+        self.occupied[level] |= 1 << slot;
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (level, slot, prev, next) = {
+            let e = &self.entries[index];
+            (e.level, e.slot, e.prev, e.next)
+        };
+        if prev == NIL {
+            self.heads[level][slot] = next;
+            if next == NIL {
+                self.occupied[level] &= !(1 << slot);
+            }
+        } else {
+            self.entries[prev].next = next;
+        }
+        if next != NIL {
+            self.entries[next].prev = prev;
+        }
+    }
+
+    // The non-empty slot starting first, as (level, slot, start tick).
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        let mut best: Option<(usize, usize, u64)> = None;
+        for level in 0..LEVELS {
+            let shift = SLOT_BITS * level as u64;
+            let current = ((self.elapsed >> shift) as usize) & (SLOTS - 1);
+            let range = shift + SLOT_BITS;
+            let base = self.elapsed & !((1u64 << range) - 1);
+            let (slot, start) = if level < LEVELS - 1 {
+                let ahead = self.occupied[level] & (!0u64 << current);
+                if ahead == 0 {
+                    continue;
+                }
+                let slot = ahead.trailing_zeros() as usize;
+                (slot, base + ((slot as u64) << shift))
+            } else {
+                // The top level wraps: look from the slot after the current
+                // one, a slot at or behind it is on the next turn.
+                let first = (current + 1) % SLOTS;
+                let ahead = self.occupied[level].rotate_right(first as u32);
+                if ahead == 0 {
+                    continue;
+                }
+                let slot = (first + ahead.trailing_zeros() as usize) % SLOTS;
+                let turn = if slot <= current { 1u64 << range } else { 0 };
+                (slot, base + ((slot as u64) << shift) + turn)
+            };
+            let start = start.max(self.elapsed);
+            if best.map_or(true, |(_, _, b)| start < b) {
+                best = Some((level, slot, start));
+            }
+        }
+        best
+    }
+}
+
+fn to_ticks(time: Duration) -> u64 {
+    time.as_millis().min(u64::MAX as u128) as u64
+}
+
+fn main() {
+    retry_ops();
+    session_ops();
+    check_ops();
+
+    fn retry_ops() {
+        println!("RETRY QUEUE");
+        let clock = ManualClock::new();
+        let mut q = DelayQueue::with_clock(clock.clone());
+        q.insert("retry job 1", Duration::from_millis(100));
+        q.insert("retry job 2", Duration::from_secs(5));
+        q.insert("retry job 3", Duration::from_millis(30));
+        for step in [Duration::from_millis(50), Duration::from_millis(60), Duration::from_secs(10)] {
+            clock.advance(step);
+            println!("at {:?}: {:?}", clock.now(), q.poll());
+        }
+        println!("=========================");
+    }
+
+    fn session_ops() {
+        println!("SESSION EXPIRY");
+        let clock = ManualClock::new();
+        let mut q = DelayQueue::with_clock(clock.clone());
+        let ttl = Duration::from_secs(30 * 60);
+        let alice = q.insert("alice", ttl);
+        let bob = q.insert("bob", ttl);
+        let carol = q.insert("carol", ttl);
+        // Alice keeps using the site, Carol logs out.
+        clock.advance(Duration::from_secs(20 * 60));
+        q.reset(alice, ttl);
+        println!("carol logged out: {:?}", q.remove(carol));
+        clock.advance(Duration::from_secs(15 * 60));
+        println!("expired after 35 min: {:?}, alice has {:?} left", q.poll(), q.remaining(alice));
+        println!("bob's key is stale: reset {}", q.reset(bob, ttl));
+        clock.advance(Duration::from_secs(20 * 60));
+        println!("expired after 55 min: {:?}, len {}", q.poll(), q.len());
+        println!("=========================");
+    }
+
+    fn check_ops() {
+        println!("RANDOMISED CHECK");
+        // Compare with a plain list of (deadline, id) over random operations
+        // spanning several wheel levels, once from the start and once from
+        // just before the top level wraps round, with delays up to a full
+        // turn.
+        check(0, 5_000_000);
+        check((1 << 36) - 300_000, MAX_TICKS + 1_000);
+        println!("=========================");
+
+        fn check(start: u64, max_delay: u64) {
+            let mut seed: u64 = 0x2545F4914F6CDD1D;
+            let mut next = move |n: u64| {
+                seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17;
+                seed % n
+            };
+            let clock = ManualClock::new();
+            clock.advance(Duration::from_millis(start));
+            let mut q = DelayQueue::with_clock(clock.clone());
+            let mut model: Vec<(u64, u64, Key)> = Vec::new();
+            let mut id = 0;
+            let mut polled = 0;
+            for _ in 0..20_000 {
+                let now = clock.now().as_millis() as u64;
+                match next(10) {
+                    0..=4 => {
+                        let delay = match next(3) { 0 => next(64), 1 => next(10_000), _ => next(max_delay) };
+                        let key = q.insert(id, Duration::from_millis(delay));
+                        model.push((now + delay.min(MAX_TICKS), id, key));
+                        id += 1;
+                    }
+                    5 if !model.is_empty() => {
+                        let i = next(model.len() as u64) as usize;
+                        let (_, item, key) = model.swap_remove(i);
+                        assert_eq!(q.remove(key), Some(item));
+                    }
+                    6 if !model.is_empty() => {
+                        let i = next(model.len() as u64) as usize;
+                        let delay = next(100_000);
+                        assert!(q.reset(model[i].2, Duration::from_millis(delay)));
+                        model[i].0 = now + delay;
+                    }
+                    _ => {
+                        // Now and then a jump long enough to expire the
+                        // longest delays.
+                        let step = if next(50) == 0 { next(max_delay) } else { next(200_000) };
+                        clock.advance(Duration::from_millis(step));
+                        let now = clock.now().as_millis() as u64;
+                        let mut due: Vec<(u64, u64)> = model.iter()
+                            .filter(|e| e.0 <= now).map(|e| (e.0, e.1)).collect();
+                        due.sort();
+                        model.retain(|e| e.0 > now);
+                        let got: Vec<(u64, u64)> = q.poll_timed().into_iter()
+                            .map(|(deadline, item)| (deadline.as_millis() as u64, item)).collect();
+                        // The queue's own order is by deadline.
+                        assert!(got.windows(2).all(|w| w[0].0 <= w[1].0), "out of order: {:?}", got);
+                        let mut got_sorted = got.clone();
+                        got_sorted.sort();
+                        assert_eq!(got_sorted, due);
+                        polled += got.len();
+                    }
+                }
+                assert_eq!(q.len(), model.len());
+            }
+            println!("from {} ms: 20000 random operations agree with a plain list, {} expired",
+                     start, polled);
+        }
+    }
+}
@@ -4,7 +4,19 @@
 /// right and evaluates them to find the answer.
 /// This program leverages stack data structure.
 ///
+/// Numbers can carry units, "60 km / 2 h in m/s" is checked for dimensions
+/// and converted.
+///
+/// Run without arguments for an interactive calculator, or pass a file with
+/// one expression per line to evaluate it as a script. `--check` runs the
+/// REPL's own checks instead.
+///
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::process;
 
 #[derive(Debug)]
 struct Stack<T> {
@@ -116,6 +128,37 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Lifo<T>: Container {
+    fn push(&mut self, val: T) -> Result<(), String>;
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<&T>;
+}
+
+impl<T> Container for Stack<T> {
+    fn len(&self) -> usize { Stack::len(self) }
+    fn clear(&mut self) { Stack::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Lifo<T> for Stack<T> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        Stack::push(self, val);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> { Stack::pop(self) }
+    fn peek(&self) -> Option<&T> { Stack::peek(self) }
+}
+
 /// Parenthesis checker.
 fn par_match(open: char, close: char) -> bool {
     let opens = "({[";
@@ -154,103 +197,846 @@ fn par_checker(par: &str) -> bool {
     balance && stack.is_empty()
 }
 
-/// Converting infix expression to postfix.
-fn infix_to_postfix(infix: &str) -> Option<String> {
-    // Check if parentheses are balanced.
-    if !par_checker(infix) {
-        return None
+/// Error raised while reading or evaluating an expression.
+/// `pos` is the byte offset of the offending token so it can be pointed at.
+#[derive(Debug, Clone, PartialEq)]
+struct CalcError {
+    pos: usize,
+    msg: String,
+}
+
+impl CalcError {
+    fn new(pos: usize, msg: &str) -> Self {
+        Self { pos, msg: msg.to_string() }
+    }
+}
+
+/// A token of the expression and where it starts in the input.
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    pos: usize,
+}
+
+impl Token {
+    fn is_number(&self) -> bool {
+        self.text.starts_with(|c: char| c.is_ascii_digit())
+            && self.text.chars().all(|c| c.is_ascii_digit() || c == '.')
+    }
+
+    // A number with a unit written after it, like "20cm".
+    fn is_quantity(&self) -> bool {
+        self.text.starts_with(|c: char| c.is_ascii_digit()) && !self.is_number()
+    }
+
+    fn is_name(&self) -> bool {
+        self.text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && self.text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    fn is_operand(&self) -> bool {
+        self.is_number() || self.is_quantity() || self.is_name()
+    }
+}
+
+/// Split an expression into numbers, names, operators and brackets.
+/// Tokens no longer need to be separated by spaces: "(2+3)*x" works.
+/// A unit right after a number is part of it, "20 cm" is one token "20cm".
+fn tokenize(expr: &str) -> Result<Vec<Token>, CalcError> {
+    let chars: Vec<(usize, char)> = expr.char_indices().collect();
+    // Byte offset of char `index`, the end of the input past the last one.
+    let offset = |index: usize| if index < chars.len() { chars[index].0 } else { expr.len() };
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let (pos, c) = chars[index];
+        if c.is_whitespace() {
+            index += 1;
+        } else if c.is_ascii_digit() {
+            // Numbers are runs of digits with at most one decimal point.
+            let mut end = index;
+            let mut seen_dot = false;
+            while end < chars.len() {
+                let n = chars[end].1;
+                if n == '.' && !seen_dot
+                    && end + 1 < chars.len() && chars[end + 1].1.is_ascii_digit()
+                {
+                    seen_dot = true;
+                } else if !n.is_ascii_digit() {
+                    break;
+                }
+                end += 1;
+            }
+            tokens.push(Token { text: expr[pos..offset(end)].to_string(), pos });
+            index = end;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            // Names are runs of letters, digits and underscores.
+            let mut end = index;
+            while end < chars.len() && (chars[end].1.is_ascii_alphanumeric() || chars[end].1 == '_') {
+                end += 1;
+            }
+            let name = &expr[pos..offset(end)];
+            match tokens.last_mut() {
+                Some(last) if last.is_number() && find_unit(name).is_some() => {
+                    last.text.push_str(name);
+                }
+                _ => tokens.push(Token { text: name.to_string(), pos }),
+            }
+            index = end;
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token { text: c.to_string(), pos });
+            index += 1;
+        } else {
+            return Err(CalcError::new(pos, &format!("unexpected character {:?}", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// UNITS.
+/// Quantities are kept in base SI units. `dims` holds the exponents of
+/// m, kg, s, A, K, mol and cd, in that order.
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+// (name, size in base units, dimension, takes a prefix)
+const UNITS: [(&str, f64, [i32; 7], bool); 15] = [
+    ("m", 1.0, [1, 0, 0, 0, 0, 0, 0], true),
+    ("g", 1e-3, [0, 1, 0, 0, 0, 0, 0], true),
+    ("s", 1.0, [0, 0, 1, 0, 0, 0, 0], true),
+    ("A", 1.0, [0, 0, 0, 1, 0, 0, 0], true),
+    ("K", 1.0, [0, 0, 0, 0, 1, 0, 0], true),
+    ("mol", 1.0, [0, 0, 0, 0, 0, 1, 0], true),
+    ("cd", 1.0, [0, 0, 0, 0, 0, 0, 1], true),
+    ("min", 60.0, [0, 0, 1, 0, 0, 0, 0], false),
+    ("h", 3600.0, [0, 0, 1, 0, 0, 0, 0], false),
+    ("L", 1e-3, [3, 0, 0, 0, 0, 0, 0], true),
+    ("Hz", 1.0, [0, 0, -1, 0, 0, 0, 0], true),
+    ("N", 1.0, [1, 1, -2, 0, 0, 0, 0], true),
+    ("Pa", 1.0, [-1, 1, -2, 0, 0, 0, 0], true),
+    ("J", 1.0, [2, 1, -2, 0, 0, 0, 0], true),
+    ("W", 1.0, [2, 1, -3, 0, 0, 0, 0], true),
+];
+
+const PREFIXES: [(&str, f64); 7] = [
+    ("G", 1e9), ("M", 1e6), ("k", 1e3), ("c", 1e-2), ("m", 1e-3), ("u", 1e-6), ("n", 1e-9),
+];
+
+/// A number and its dimension.
+/// `unit` is set by `in` conversions: the unit to show and its size.
+#[derive(Debug, Clone, PartialEq)]
+struct Quantity {
+    value: f64,
+    dims: [i32; 7],
+    unit: Option<(String, f64)>,
+}
+
+impl Quantity {
+    fn number(value: f64) -> Self {
+        Self { value, dims: [0; 7], unit: None }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.unit {
+            Some((name, size)) => write!(f, "{} {}", format_number(self.value / size), name),
+            None if self.dims == [0; 7] => write!(f, "{}", format_number(self.value)),
+            None => write!(f, "{} {}", format_number(self.value), dims_to_string(&self.dims)),
+        }
+    }
+}
+
+// Twelve significant digits hide the noise of floating point, 0.1 + 0.2
+// prints as 0.3.
+fn format_number(value: f64) -> String {
+    let rounded: f64 = format!("{:.11e}", value).parse().unwrap();
+    rounded.to_string()
+}
+
+// Base units with positive exponents over those with negative ones,
+// e.g. "kg*m/s^2".
+fn dims_to_string(dims: &[i32; 7]) -> String {
+    let mut num = Vec::new();
+    let mut den = Vec::new();
+    for (unit, &exp) in BASE_UNITS.iter().zip(dims.iter()) {
+        let part = |exp: i32| if exp == 1 { unit.to_string() } else { format!("{}^{}", unit, exp) };
+        if exp > 0 {
+            num.push(part(exp));
+        } else if exp < 0 {
+            den.push(part(-exp));
+        }
+    }
+    let num = if num.is_empty() { "1".to_string() } else { num.join("*") };
+    match den.len() {
+        0 => num,
+        1 => format!("{}/{}", num, den[0]),
+        _ => format!("{}/({})", num, den.join("*")),
+    }
+}
+
+/// Look a unit up by name, trying the name as is and then with a prefix.
+fn find_unit(name: &str) -> Option<Quantity> {
+    let unit = |value: f64, dims: [i32; 7]| Some(Quantity { value, dims, unit: None });
+    for &(unit_name, size, dims, _) in UNITS.iter() {
+        if unit_name == name {
+            return unit(size, dims);
+        }
+    }
+    for &(prefix, scale) in PREFIXES.iter() {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            for &(unit_name, size, dims, prefixed) in UNITS.iter() {
+                if prefixed && unit_name == rest {
+                    return unit(scale * size, dims);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// One step of the stack algorithms, reported to a `Tracer`.
+/// `stack` is listed bottom to top, `output` in the order it was produced.
+#[derive(Debug, Clone)]
+struct TraceStep {
+    phase: &'static str,
+    token: String,
+    action: String,
+    stack: Vec<String>,
+    output: Vec<String>,
+}
+
+/// Observer hook for `infix_to_postfix_traced` and `postfix_eval_traced`.
+/// `step` is called once for every token handled.
+trait Tracer {
+    fn step(&mut self, step: TraceStep);
+}
+
+/// Tracer used by the plain versions of the algorithms.
+struct NoTrace;
+
+impl Tracer for NoTrace {
+    fn step(&mut self, _step: TraceStep) {}
+}
+
+/// Collects the steps and renders them as an ASCII table.
+struct TableTracer {
+    steps: Vec<TraceStep>,
+}
+
+impl TableTracer {
+    fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    fn render(&self) -> String {
+        let header = ["token", "action", "stack", "output"];
+        let rows: Vec<[String; 4]> = self.steps.iter()
+            .map(|step| [
+                step.token.clone(),
+                step.action.clone(),
+                step.stack.join(" "),
+                step.output.join(" "),
+            ])
+            .collect();
+
+        // Every column is as wide as its widest cell.
+        let mut widths = header.map(|h| h.len());
+        for row in rows.iter() {
+            for (col, cell) in row.iter().enumerate() {
+                widths[col] = widths[col].max(cell.chars().count());
+            }
+        }
+        let border = widths.iter()
+            .map(|w| "-".repeat(w + 2))
+            .collect::<Vec<_>>()
+            .join("+");
+        let border = format!("+{}+\n", border);
+        let line = |cells: [&str; 4]| {
+            let cells: Vec<String> = cells.iter().enumerate()
+                .map(|(col, cell)| format!(" {:<w$} ", cell, w = widths[col]))
+                .collect();
+            format!("|{}|\n", cells.join("|"))
+        };
+
+        let mut out = border.clone();
+        out += &line(header);
+        out += &border;
+        for row in rows.iter() {
+            out += &line([&row[0], &row[1], &row[2], &row[3]]);
+        }
+        out += &border;
+        out
+    }
+}
+
+impl Tracer for TableTracer {
+    fn step(&mut self, step: TraceStep) {
+        self.steps.push(step);
+    }
+}
+
+/// Writes every step as one JSON object per line.
+struct JsonTracer<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonTracer<W> {
+    fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> Tracer for JsonTracer<W> {
+    fn step(&mut self, step: TraceStep) {
+        let list = |items: &[String]| {
+            let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+            format!("[{}]", items.join(","))
+        };
+        let _ = writeln!(
+            self.out,
+            "{{\"phase\":{},\"token\":{},\"action\":{},\"stack\":{},\"output\":{}}}",
+            json_string(step.phase),
+            json_string(&step.token),
+            json_string(&step.action),
+            list(&step.stack),
+            list(&step.output),
+        );
+    }
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
     }
+    out.push('"');
+    out
+}
+
+/// Converting infix expression to postfix.
+fn infix_to_postfix(infix: &str) -> Result<Vec<Token>, CalcError> {
+    infix_to_postfix_traced(infix, &mut NoTrace)
+}
+
+/// Converting infix expression to postfix, reporting every step to `tracer`.
+/// Brackets and the order of operands and operators are checked on the way
+/// so that errors carry the position of the token at fault.
+fn infix_to_postfix_traced(infix: &str, tracer: &mut dyn Tracer) -> Result<Vec<Token>, CalcError> {
     // Set priority of all symbols.
     let mut prec = HashMap::new();
     prec.insert("(", 1); prec.insert(")", 1);
     prec.insert("+", 2); prec.insert("-", 2);
     prec.insert("*", 3); prec.insert("/", 3);
 
-    // ops: svae operators, postfix: svae postfix expression
-    let mut ops = Stack::new();
-    let mut postfix = Vec::new();
-    for token in infix.split_whitespace() {
-        // Characters 0-9 and A-Z onto stack.
-        if ("A" <= token && token <= "Z") || ("0" <= token && token <= "9") {
+    // ops: save operators, postfix: save postfix expression
+    let mut ops: Stack<Token> = Stack::new();
+    let mut postfix: Vec<Token> = Vec::new();
+    let mut report = |token: &str, action: String, ops: &Stack<Token>, postfix: &[Token]| {
+        tracer.step(TraceStep {
+            phase: "infix_to_postfix",
+            token: token.to_string(),
+            action,
+            stack: ops.data.iter().map(|t| t.text.clone()).collect(),
+            output: postfix.iter().map(|t| t.text.clone()).collect(),
+        });
+    };
+    // An operand is expected at the start and after every operator.
+    let mut want_operand = true;
+    for token in tokenize(infix)? {
+        let text = token.text.clone();
+        let action;
+        if token.is_operand() {
+            if !want_operand {
+                return Err(CalcError::new(token.pos, "expected an operator"));
+            }
             postfix.push(token);
-        } else if "(" == token {
+            want_operand = false;
+            action = "output operand".to_string();
+        } else if "(" == token.text {
+            if !want_operand {
+                return Err(CalcError::new(token.pos, "expected an operator"));
+            }
             ops.push(token);
-        } else if ")" == token {
-            let mut top = ops.pop().unwrap();
-            while top != "(" {
-                postfix.push(top);
-                top = ops.pop().unwrap();
+            action = "push '('".to_string();
+        } else if ")" == token.text {
+            if want_operand {
+                return Err(CalcError::new(token.pos, "expected a number or name"));
+            }
+            let mut popped = 0;
+            loop {
+                match ops.pop() {
+                    Some(top) if top.text == "(" => break,
+                    Some(top) => { postfix.push(top); popped += 1; }
+                    None => return Err(CalcError::new(token.pos, "unmatched ')'")),
+                }
             }
+            action = format!("output {} operator(s), drop '('", popped);
         } else {
-            while (!ops.is_empty()) && (prec[ops.peek().unwrap()] >= prec[token]) {
+            if want_operand {
+                return Err(CalcError::new(token.pos, "expected a number or name"));
+            }
+            let mut popped = 0;
+            while !ops.is_empty()
+                && prec[ops.peek().unwrap().text.as_str()] >= prec[token.text.as_str()]
+            {
                 postfix.push(ops.pop().unwrap());
+                popped += 1;
             }
             ops.push(token);
+            want_operand = true;
+            action = if popped == 0 {
+                "push operator".to_string()
+            } else {
+                format!("output {} operator(s), push operator", popped)
+            };
         }
+        report(&text, action, &ops, &postfix);
     }
-
-    // Push the remaining operators into stack.
-    while !ops.is_empty() {
-        postfix.push(ops.pop().unwrap());
+    if want_operand {
+        return Err(CalcError::new(infix.len(), "unexpected end of expression"));
     }
 
-    // pop out operators and create the postfix expression.
-    let mut postfix_str = "".to_string();
-    for c in postfix {
-        postfix_str += &c.to_string();
-        postfix_str += "";
+    // Push the remaining operators into postfix.
+    while let Some(top) = ops.pop() {
+        if top.text == "(" {
+            return Err(CalcError::new(top.pos, "unmatched '('"));
+        }
+        postfix.push(top);
+        report("(end)", "output remaining operator".to_string(), &ops, &postfix);
     }
-    Some(postfix_str)
+    Ok(postfix)
+}
+
+/// Render postfix tokens as a space separated string.
+fn postfix_to_string(postfix: &[Token]) -> String {
+    let texts: Vec<&str> = postfix.iter().map(|t| t.text.as_str()).collect();
+    texts.join(" ")
 }
 
 /// Evaluate postfix operator
-fn postfix_eval(postfix: &str) -> Option<i32> {
-    // The expression needs atleast two operands and one operator, and two 
-    // spaces to separate them.
-    if postfix.len() < 5 { return None; }
-    let mut ops = Stack::new();
-
-    for token in postfix.split_whitespace() {
-        // Strings can be compared directly.
-        if "0" <= token && token <= "9" {
-            ops.push(token.parse::<i32>().unwrap());
+/// Names are looked up in `vars` first, then taken as units.
+fn postfix_eval(postfix: &[Token], vars: &HashMap<String, Quantity>) -> Result<Quantity, CalcError> {
+    postfix_eval_traced(postfix, vars, &mut NoTrace)
+}
+
+/// Evaluate postfix operator, reporting every step to `tracer`.
+/// There is no output queue here, only the operand stack.
+fn postfix_eval_traced(
+    postfix: &[Token],
+    vars: &HashMap<String, Quantity>,
+    tracer: &mut dyn Tracer,
+) -> Result<Quantity, CalcError> {
+    let mut ops: Stack<Quantity> = Stack::new();
+    for token in postfix {
+        let action;
+        if token.is_number() || token.is_quantity() {
+            // Split "20cm" into the number and its unit.
+            let split = token.text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(token.text.len());
+            let num = token.text[..split].parse::<f64>()
+                .map_err(|_| CalcError::new(token.pos, "invalid number"))?;
+            let mut val = Quantity::number(num);
+            if split < token.text.len() {
+                let unit = find_unit(&token.text[split..]).unwrap();
+                val = Quantity { value: num * unit.value, dims: unit.dims, unit: None };
+            }
+            ops.push(val);
+            action = "push number".to_string();
+        } else if token.is_name() {
+            if let Some(val) = vars.get(&token.text) {
+                ops.push(val.clone());
+                action = "push variable".to_string();
+            } else if let Some(unit) = find_unit(&token.text) {
+                ops.push(unit);
+                action = "push unit".to_string();
+            } else {
+                let msg = format!("unknown variable or unit '{}'", token.text);
+                return Err(CalcError::new(token.pos, &msg));
+            }
         } else {
             // For subtraction and division, the order matters.
-            let op2 = ops.pop().unwrap();
-            let op1 = ops.pop().unwrap();
-            let res = do_calc(token, op1, op2);
+            let (op2, op1) = match (ops.pop(), ops.pop()) {
+                (Some(op2), Some(op1)) => (op2, op1),
+                _ => return Err(CalcError::new(token.pos, "missing operand")),
+            };
+            let res = do_calc(&token.text, &op1, &op2)
+                .map_err(|msg| CalcError::new(token.pos, &msg))?;
+            action = format!("pop {} and {}, push {}", op1, op2, res);
             ops.push(res);
         }
+        tracer.step(TraceStep {
+            phase: "postfix_eval",
+            token: token.text.clone(),
+            action,
+            stack: ops.data.iter().map(|v| v.to_string()).collect(),
+            output: Vec::new(),
+        });
     }
     // The value remaining stack is the result
-    Some(ops.pop().unwrap())
+    match (ops.pop(), ops.is_empty()) {
+        (Some(res), true) => Ok(res),
+        _ => Err(CalcError::new(0, "malformed postfix expression")),
+    }
 }
 
 /// Do calc. 
-fn do_calc(op: &str, op1: i32, op2: i32) -> i32 {
-    if "+" == op {
-        op1 + op2
-    } else if "-" == op {
-        op1 - op2
+/// Only quantities of the same dimension can be added or subtracted.
+fn do_calc(op: &str, op1: &Quantity, op2: &Quantity) -> Result<Quantity, String> {
+    let mut dims = op1.dims;
+    let value = if "+" == op || "-" == op {
+        if op1.dims != op2.dims {
+            let verb = if "+" == op { "add" } else { "subtract" };
+            return Err(format!("cannot {} {} and {}", verb,
+                               dims_to_string(&op1.dims), dims_to_string(&op2.dims)));
+        }
+        if "+" == op { op1.value + op2.value } else { op1.value - op2.value }
     } else if "*" == op {
-        op1 * op2
+        for (d, o) in dims.iter_mut().zip(&op2.dims) { *d += o; }
+        op1.value * op2.value
     } else if "/" == op {
-        if 0 == op2 {
-            panic!("ZeroDivisionError: Invalid operation!");
+        if 0.0 == op2.value {
+            return Err("division by zero".to_string());
         }
-        op1/op2
+        for (d, o) in dims.iter_mut().zip(&op2.dims) { *d -= o; }
+        op1.value / op2.value
     } else {
-        panic!("OperatorError: Invalid operator: {:?}", op);
+        return Err(format!("invalid operator {:?}", op));
+    };
+    if !value.is_finite() {
+        return Err("arithmetic overflow".to_string());
+    }
+    Ok(Quantity { value, dims, unit: None })
+}
+
+/// An expression, and the position of `in` with the unit text if it has one.
+type Conversion<'a> = (&'a str, Option<(usize, &'a str)>);
+
+/// Split off a trailing "in <unit>" conversion.
+fn split_conversion(expr: &str) -> Result<Conversion<'_>, CalcError> {
+    for token in tokenize(expr)? {
+        if token.text == "in" {
+            let unit = &expr[token.pos + token.text.len()..];
+            return Ok((&expr[..token.pos], Some((token.pos, unit))));
+        }
+    }
+    Ok((expr, None))
+}
+
+/// Evaluate an expression, converting the result if it ends in "in <unit>".
+fn eval_expr(expr: &str, vars: &HashMap<String, Quantity>) -> Result<Quantity, CalcError> {
+    eval_expr_traced(expr, vars, &mut NoTrace)
+}
+
+fn eval_expr_traced(
+    expr: &str,
+    vars: &HashMap<String, Quantity>,
+    tracer: &mut dyn Tracer,
+) -> Result<Quantity, CalcError> {
+    let (body, conversion) = split_conversion(expr)?;
+    let postfix = infix_to_postfix_traced(body, tracer)?;
+    let mut val = postfix_eval_traced(&postfix, vars, tracer)?;
+    if let Some((in_pos, unit_text)) = conversion {
+        // The target is made of units only, variables do not apply.
+        let offset = in_pos + 2;
+        let unit = infix_to_postfix(unit_text)
+            .and_then(|postfix| postfix_eval(&postfix, &HashMap::new()))
+            .map_err(|err| CalcError { pos: err.pos + offset, ..err })?;
+        if unit.dims != val.dims {
+            let msg = format!("cannot convert {} to {}",
+                              dims_to_string(&val.dims), dims_to_string(&unit.dims));
+            return Err(CalcError::new(in_pos, &msg));
+        }
+        val.unit = Some((unit_text.trim().to_string(), unit.value));
+    }
+    Ok(val)
+}
+
+/// Expression tree, built from the postfix form.
+#[derive(Debug)]
+enum Ast {
+    Operand(Token),
+    BinOp(Token, Box<Ast>, Box<Ast>),
+}
+
+fn postfix_to_ast(postfix: &[Token]) -> Result<Ast, CalcError> {
+    let mut nodes = Stack::new();
+    for token in postfix {
+        if token.is_operand() {
+            nodes.push(Ast::Operand(token.clone()));
+        } else {
+            let (rhs, lhs) = match (nodes.pop(), nodes.pop()) {
+                (Some(rhs), Some(lhs)) => (rhs, lhs),
+                _ => return Err(CalcError::new(token.pos, "missing operand")),
+            };
+            nodes.push(Ast::BinOp(token.clone(), Box::new(lhs), Box::new(rhs)));
+        }
+    }
+    match (nodes.pop(), nodes.is_empty()) {
+        (Some(root), true) => Ok(root),
+        _ => Err(CalcError::new(0, "malformed postfix expression")),
+    }
+}
+
+/// Prefix form is a pre-order walk of the tree.
+fn ast_to_prefix(ast: &Ast) -> String {
+    match ast {
+        Ast::Operand(token) => token.text.clone(),
+        Ast::BinOp(op, lhs, rhs) => {
+            format!("{} {} {}", op.text, ast_to_prefix(lhs), ast_to_prefix(rhs))
+        }
+    }
+}
+
+/// Draw the tree one node per line, children indented under their operator.
+fn render_ast(ast: &Ast, depth: usize, out: &mut String) {
+    out.push_str(&"  ".repeat(depth));
+    match ast {
+        Ast::Operand(token) => {
+            out.push_str(&token.text);
+            out.push('\n');
+        }
+        Ast::BinOp(op, lhs, rhs) => {
+            out.push_str(&op.text);
+            out.push('\n');
+            render_ast(lhs, depth + 1, out);
+            render_ast(rhs, depth + 1, out);
+        }
+    }
+}
+
+fn expression_calc(expr: &str) -> Option<Quantity> {
+    eval_expr(expr, &HashMap::new()).ok()
+}
+
+/// CALCULATOR REPL.
+/// Keeps the result of the last evaluation in `ans` and the `let` bindings
+/// between lines.
+struct Repl {
+    vars: HashMap<String, Quantity>,
+}
+
+impl Repl {
+    fn new() -> Self {
+        Self { vars: HashMap::new() }
+    }
+
+    // Run one line of input. Returns the text to print, if any.
+    // Error positions are relative to the start of `line`.
+    fn run_line(&mut self, line: &str) -> Result<Option<String>, CalcError> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return Ok(None);
+        }
+        if let Some(cmd) = trimmed.strip_prefix(':') {
+            let name = cmd.split_whitespace().next().unwrap_or("");
+            if !REPL_COMMANDS.contains(&name) {
+                let pos = line.find(':').unwrap();
+                return Err(CalcError::new(pos, &format!("unknown command ':{}'", name)));
+            }
+            let rest = &cmd[name.len()..];
+            let offset = offset_in(line, rest);
+            return self.run_command(name, rest)
+                .map_err(|err| CalcError { pos: err.pos + offset, ..err });
+        }
+        if let Some(binding) = trimmed.strip_prefix("let ") {
+            let (name, expr) = match binding.split_once('=') {
+                Some((name, expr)) => (name.trim(), expr),
+                None => {
+                    let pos = offset_in(line, binding);
+                    return Err(CalcError::new(pos, "expected 'let <name> = <expr>'"));
+                }
+            };
+            let name_pos = offset_in(line, binding.trim_start());
+            let name_tok = Token { text: name.to_string(), pos: name_pos };
+            if name.is_empty() || !name_tok.is_name() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(CalcError::new(name_pos, "invalid variable name"));
+            }
+            if name == "ans" || name == "let" || name == "in" {
+                return Err(CalcError::new(name_pos, "reserved name"));
+            }
+            // A variable would hide the unit of the same name.
+            if find_unit(name).is_some() {
+                return Err(CalcError::new(name_pos, &format!("'{}' is a unit", name)));
+            }
+            let offset = offset_in(line, expr);
+            let val = eval_expr(expr, &self.vars)
+                .map_err(|err| CalcError { pos: err.pos + offset, ..err })?;
+            let out = format!("{} = {}", name, val);
+            self.vars.insert(name.to_string(), val);
+            return Ok(Some(out));
+        }
+        let val = eval_expr(line, &self.vars)?;
+        let out = val.to_string();
+        self.vars.insert("ans".to_string(), val);
+        Ok(Some(out))
+    }
+
+    // `:postfix`, `:prefix` and `:ast` show the intermediate forms.
+    fn run_command(&mut self, name: &str, rest: &str) -> Result<Option<String>, CalcError> {
+        // The intermediate forms are shown for the part before any "in <unit>".
+        let body = split_conversion(rest)?.0;
+        match name {
+            "postfix" => {
+                let postfix = infix_to_postfix(body)?;
+                Ok(Some(postfix_to_string(&postfix)))
+            }
+            "prefix" => {
+                let ast = postfix_to_ast(&infix_to_postfix(body)?)?;
+                Ok(Some(ast_to_prefix(&ast)))
+            }
+            "ast" => {
+                let ast = postfix_to_ast(&infix_to_postfix(body)?)?;
+                let mut out = String::new();
+                render_ast(&ast, 0, &mut out);
+                Ok(Some(out.trim_end().to_string()))
+            }
+            "trace" | "trace_json" => {
+                // Both phases go to the same tracer, so one table or one
+                // stream of JSON lines covers the whole evaluation.
+                let mut table = TableTracer::new();
+                let mut json = JsonTracer::new(Vec::new());
+                let tracer: &mut dyn Tracer = if name == "trace" { &mut table } else { &mut json };
+                let val = eval_expr_traced(rest, &self.vars, tracer)?;
+                let out = if name == "trace" {
+                    table.render()
+                } else {
+                    String::from_utf8(json.out).unwrap()
+                };
+                Ok(Some(format!("{}= {}", out, val)))
+            }
+            "vars" => {
+                let mut names: Vec<&String> = self.vars.keys().collect();
+                names.sort();
+                let lines: Vec<String> = names.iter()
+                    .map(|name| format!("{} = {}", name, self.vars[*name]))
+                    .collect();
+                Ok(Some(lines.join("\n")))
+            }
+            _ => Ok(Some(REPL_HELP.to_string())),
+        }
     }
 }
 
-fn expression_calc(expr: &str) -> Option<i32> {
-    let infix_to_postfix = infix_to_postfix(expr)?;
-    postfix_eval(&infix_to_postfix)
+const REPL_COMMANDS: [&str; 7] = [
+    "postfix", "prefix", "ast", "trace", "trace_json", "vars", "help",
+];
+
+const REPL_HELP: &str = "\
+<expr>            evaluate, the result is kept in `ans`
+<expr> in <unit>  evaluate and convert, e.g. 60 km / 2 h in m/s
+let x = <expr>    bind a variable
+:postfix <expr>   show the postfix form
+:prefix <expr>    show the prefix form
+:ast <expr>       show the expression tree
+:trace <expr>     show every stack step as a table
+:trace_json <e>   show every stack step as JSON lines
+:vars             list variables
+:quit             leave";
+
+/// Byte position of `part`, a slice of `line`, within `line`.
+fn offset_in(line: &str, part: &str) -> usize {
+    part.as_ptr() as usize - line.as_ptr() as usize
+}
+
+/// Caret line pointing at `pos` of the echoed input, `indent` columns in.
+fn caret_line(line: &str, pos: usize, indent: usize) -> String {
+    let width = line[..pos.min(line.len())].chars().count();
+    format!("{}^", " ".repeat(indent + width))
+}
+
+/// Evaluate every line of `input`. In interactive mode a prompt is shown and
+/// the caret lines up with it, otherwise failing lines are echoed first.
+fn run_repl<R: BufRead>(input: R, interactive: bool) -> bool {
+    let prompt = "> ";
+    let mut repl = Repl::new();
+    let mut all_ok = true;
+    if interactive {
+        println!("Expression calculator, :help for commands.");
+    }
+    let mut lines = input.lines();
+    loop {
+        if interactive {
+            print!("{}", prompt);
+            io::stdout().flush().unwrap();
+        }
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        if matches!(line.trim(), ":quit" | ":q") {
+            break;
+        }
+        match repl.run_line(&line) {
+            Ok(Some(out)) => println!("{}", out),
+            Ok(None) => {}
+            Err(err) => {
+                all_ok = false;
+                if interactive {
+                    println!("{}", caret_line(&line, err.pos, prompt.len()));
+                } else {
+                    println!("{}", line);
+                    println!("{}", caret_line(&line, err.pos, 0));
+                }
+                println!("error: {}", err.msg);
+            }
+        }
+    }
+    all_ok
 }
 
 fn main() {
-    let expression = "( ( 2 + 3 ) * 4 )";
-    let res = expression_calc(expression);
-    println!("{} = {:?}", expression, res);
+    // With a file argument the expressions are read from the file,
+    // otherwise from stdin.
+    let ok = match env::args().nth(1) {
+        Some(ref flag) if flag == "--check" => {
+            check_repl();
+            true
+        }
+        Some(path) => {
+            let file = File::open(&path).unwrap_or_else(|err| {
+                eprintln!("cannot open {}: {}", path, err);
+                process::exit(2);
+            });
+            run_repl(BufReader::new(file), false)
+        }
+        None => {
+            let stdin = io::stdin();
+            let interactive = stdin.is_terminal();
+            run_repl(stdin.lock(), interactive)
+        }
+    };
+    if !ok {
+        process::exit(1);
+    }
+}
+
+/// Error positions and reserved names, line by line: the input, the error
+/// position (None if it succeeds) and the output or error message.
+fn check_repl() {
+    let mut repl = Repl::new();
+    let cases = [
+        ("1 + 2", None, "3"),
+        ("1 + )", Some(4), "expected a number or name"),
+        // Trailing whitespace does not move the caret.
+        ("let y = 1 + )    ", Some(12), "expected a number or name"),
+        (":postfix 1 + )    ", Some(13), "expected a number or name"),
+        ("  let z   ", Some(6), "expected 'let <name> = <expr>'"),
+        ("let in = 3", Some(4), "reserved name"),
+        ("let ans = 3", Some(4), "reserved name"),
+        ("let km = 2", Some(4), "'km' is a unit"),
+        ("let d = 3 km  ", None, "d = 3000 m"),
+    ];
+    for &(line, pos, text) in cases.iter() {
+        match repl.run_line(line) {
+            Ok(out) => assert_eq!((None, out.unwrap_or_default()), (pos, text.to_string()), "for {:?}", line),
+            Err(err) => assert_eq!((Some(err.pos), err.msg), (pos, text.to_string()), "for {:?}", line),
+        }
+    }
+    println!("{} REPL checks passed", cases.len());
 }
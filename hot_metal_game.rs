@@ -15,6 +15,7 @@
 /// Cryptography and security; Secure message transmission. The choice of surviving 
 /// node or system can represent secure channels or keys.  
 ///
+use std::collections::VecDeque;
 
 #[derive(Debug)]
 struct Queue<T> {
@@ -48,13 +49,59 @@ impl<T> Queue<T> {
     }
 }
 
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Fifo<T>: Container {
+    fn enqueue(&mut self, val: T) -> Result<(), String>;
+    fn dequeue(&mut self) -> Option<T>;
+}
+
+impl<T> Container for Queue<T> {
+    fn len(&self) -> usize { Queue::len(self) }
+    fn clear(&mut self) { Queue::clear(self) }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+impl<T> Fifo<T> for Queue<T> {
+    fn enqueue(&mut self, val: T) -> Result<(), String> { Queue::enqueue(self, val) }
+    fn dequeue(&mut self) -> Option<T> { Queue::dequeue(self) }
+}
+
+// The standard library's ring buffer as another backing, unbounded.
+impl<T> Container for VecDeque<T> {
+    fn len(&self) -> usize { VecDeque::len(self) }
+    fn clear(&mut self) { VecDeque::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Fifo<T> for VecDeque<T> {
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        self.push_back(val);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<T> { self.pop_front() }
+}
+
 fn hot_metal(names: Vec<&str>, num: usize) -> &str {
+    let q = Queue::new(names.len());
+    hot_metal_with(q, names, num)
+}
+
+// The game on any queue, which must start empty.
+fn hot_metal_with<'a, Q: Fifo<&'a str>>(mut q: Q, names: Vec<&'a str>, num: usize) -> &'a str {
     // Adding players to a queue.
     // name: is a collection of the name of the players.
     // num: is the number of players to pass the hot metal to 
     // before elimination.
     //
-    let mut q = Queue::new(names.len());
     for name in names {
         q.enqueue(name);
     }
@@ -109,8 +156,10 @@ fn main() {
         // TESTING OUR HOT METAL GAME.
         println!("============ TESTING THE HOT METAL GAME ===========");
         let name = vec!["Jerry", "Tom", "Jim", "Carter", "Rose", "Xavier"];
-        let winner = hot_metal(name, 10);
+        let winner = hot_metal(name.clone(), 10);
         println!("The winner/survivor is {winner}");
+        let winner = hot_metal_with(VecDeque::new(), name, 10);
+        println!("On a VecDeque the winner/survivor is also {winner}");
         println!("===================================================");
     }
 }
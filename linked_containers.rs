@@ -0,0 +1,983 @@
+//! Linked containers.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// Linked-list versions of the Vec-backed containers: `LinkedStack` and
+/// `LinkedQueue` on singly linked nodes, `LinkedDeque` on doubly linked
+/// ones. Every push and pop is O(1), where the Vec versions shift all items
+/// on one side. They have no capacity.
+///
+/// Each one has a cursor, a position in the list where items can be
+/// inserted and removed in O(1).
+///
+/// The `Container`, `Lifo`, `Fifo` and `DoubleEnded` traits are implemented
+/// by these and by copies of `Stack`, `Queue` and `Deque` from rustack.rs, rusqueue.rs
+/// and deckrus.rs, so code written against a trait runs on either and the
+/// two can be benchmarked side by side. Outside this file they are
+/// implemented by `Stack` (rustack.rs, expression_calc.rs, bracket_checker.rs),
+/// `AggregateStack` (aggregate_stack.rs), `Queue` (rusqueue.rs,
+/// hot_metal_game.rs) and `Deque` (deckrus.rs, palindrome_checker.rs).
+///
+/// Left out on purpose, because they don't fit the `&mut self` methods:
+/// - `PersistentStack`: push and pop return a new version, the old one is
+///   never changed.
+/// - `AtomicStack`, `MpmcQueue`, `BlockingQueue`, the async channel and the
+///   work-stealing deque: shared between threads, so they work through
+///   `&self` or split handles, and block, wait or may fail to steal.
+/// - `PersistentQueue`: every operation can fail with an `io::Error`.
+/// - `PriorityQueue`, `MinMaxHeap`, the mergeable heaps and `DelayQueue`:
+///   items come out by priority or deadline, not in LIFO or FIFO order.
+/// - The `Queue` and `Deque` copies in blocking_queue.rs, async_channel.rs
+///   and window.rs are private storage of those containers, the one in
+///   ring_queue.rs is only a benchmark baseline, and the copies in
+///   snapshot.rs go through its `Snapshot` trait instead.
+///
+/// `LinkedStack` only uses `Box`. `LinkedQueue` and `LinkedDeque` link
+/// nodes with raw pointers and never hold a `Box` and a raw pointer to the
+/// same node at once, which keeps them sound under Miri's checks. The
+/// checks in `main` are small enough to run under Miri.
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::time::Instant;
+
+// CONTAINER TRAITS.
+//
+// The same operations under the same names for every container, so code
+// written against a trait runs on any backing implementation.
+
+/// What every container has, bounded or not.
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    // The most items it can hold, None if unbounded.
+    fn capacity(&self) -> Option<usize>;
+}
+
+/// Last in, first out.
+trait Lifo<T>: Container {
+    fn push(&mut self, val: T) -> Result<(), String>;
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<&T>;
+}
+
+/// First in, first out.
+trait Fifo<T>: Container {
+    fn enqueue(&mut self, val: T) -> Result<(), String>;
+    fn dequeue(&mut self) -> Option<T>;
+}
+
+/// Adding and removing at both ends.
+trait DoubleEnded<T>: Container {
+    fn push_front(&mut self, val: T) -> Result<(), String>;
+    fn push_back(&mut self, val: T) -> Result<(), String>;
+    fn pop_front(&mut self) -> Option<T>;
+    fn pop_back(&mut self) -> Option<T>;
+}
+
+// VEC-BACKED CONTAINERS.
+
+#[derive(Debug)]
+struct Stack<T> {
+    size: usize,
+    data: Vec<T>,
+}
+
+impl<T> Stack<T> {
+    fn new() -> Self {
+        Self { size: 0, data: Vec::new() }
+    }
+}
+
+impl<T> Lifo<T> for Stack<T> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        self.data.push(val);
+        self.size += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.size == 0 {
+            return None
+        }
+        self.size -= 1;
+        self.data.pop()
+    }
+
+    fn peek(&self) -> Option<&T> { self.data.last() }
+}
+
+impl<T> Container for Stack<T> {
+    fn len(&self) -> usize { self.size }
+
+    fn clear(&mut self) {
+        self.size = 0;
+        self.data.clear();
+    }
+
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+#[derive(Debug)]
+struct Queue<T> {
+    cap: usize, // Capacity
+    data: Vec<T>, // Storing elements
+}
+
+impl<T> Queue<T> {
+    fn new(size: usize) -> Self {
+        Self { cap: size, data: Vec::with_capacity(size) }
+    }
+}
+
+impl<T> Fifo<T> for Queue<T> {
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        if self.data.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<T> { self.data.pop() }
+}
+
+impl<T> Container for Queue<T> {
+    fn len(&self) -> usize { self.data.len() }
+    fn clear(&mut self) { self.data.clear() }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+#[derive(Debug)]
+struct Deque<T> {
+    cap: usize,
+    data: Vec<T>,
+}
+
+impl<T> Deque<T> {
+    fn new(cap: usize) -> Self {
+        Self { cap: cap, data: Vec::with_capacity(cap) }
+    }
+}
+
+impl<T> DoubleEnded<T> for Deque<T> {
+    fn push_front(&mut self, val: T) -> Result<(), String> {
+        if self.data.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    fn push_back(&mut self, val: T) -> Result<(), String> {
+        if self.data.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.push(val);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        if self.data.len() > 0 {
+            Some(self.data.remove(0))
+        } else {
+            None
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<T> { self.data.pop() }
+}
+
+impl<T> Container for Deque<T> {
+    fn len(&self) -> usize { self.data.len() }
+    fn clear(&mut self) { self.data.clear() }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+// LINKED STACK.
+
+type Link<T> = Option<Box<StackNode<T>>>;
+
+struct StackNode<T> {
+    val: T,
+    next: Link<T>,
+}
+
+/// Singly linked stack, the top is the head of the list.
+struct LinkedStack<T> {
+    size: usize,
+    head: Link<T>,
+}
+
+impl<T> LinkedStack<T> {
+    fn new() -> Self {
+        Self { size: 0, head: None }
+    }
+
+    fn clear(&mut self) {
+        while self.pop_node().is_some() {}
+    }
+
+    fn pop_node(&mut self) -> Option<T> {
+        self.head.take().map(|node| {
+            let node = *node;
+            self.head = node.next;
+            self.size -= 1;
+            node.val
+        })
+    }
+
+    fn peek_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.val)
+    }
+
+    // Top to bottom.
+    fn iter(&self) -> StackIter<T> {
+        StackIter { next: self.head.as_ref().map(|node| &**node) }
+    }
+
+    // A cursor on the top item.
+    fn cursor_mut(&mut self) -> StackCursorMut<T> {
+        StackCursorMut { link: Some(&mut self.head), size: &mut self.size, index: 0 }
+    }
+}
+
+impl<T> Lifo<T> for LinkedStack<T> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        let next = self.head.take();
+        self.head = Some(Box::new(StackNode { val, next }));
+        self.size += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> { self.pop_node() }
+    fn peek(&self) -> Option<&T> { self.head.as_ref().map(|node| &node.val) }
+}
+
+impl<T> Container for LinkedStack<T> {
+    fn len(&self) -> usize { self.size }
+    fn clear(&mut self) { LinkedStack::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Drop for LinkedStack<T> {
+    // Unlink one node at a time, dropping a long chain recursively could
+    // overflow the stack.
+    fn drop(&mut self) {
+        let mut cur = self.head.take();
+        while let Some(mut node) = cur {
+            cur = node.next.take();
+        }
+    }
+}
+
+struct StackIter<'a, T: 'a> {
+    next: Option<&'a StackNode<T>>,
+}
+
+impl<'a, T> Iterator for StackIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_ref().map(|node| &**node);
+            &node.val
+        })
+    }
+}
+
+/// Points at an item of a `LinkedStack`, or past the bottom. It holds the
+/// link leading to the current node, so the node can be replaced.
+struct StackCursorMut<'a, T: 'a> {
+    // Always Some, the Option lets `move_next` swap it.
+    link: Option<&'a mut Link<T>>,
+    size: &'a mut usize,
+    index: usize,
+}
+
+impl<'a, T> StackCursorMut<'a, T> {
+    // Position from the top, equal to the length past the bottom.
+    fn index(&self) -> usize { self.index }
+
+    fn current(&mut self) -> Option<&mut T> {
+        self.link.as_mut().unwrap().as_mut().map(|node| &mut node.val)
+    }
+
+    // Move one item towards the bottom. False when already past it.
+    fn move_next(&mut self) -> bool {
+        let link = self.link.take().unwrap();
+        if link.is_none() {
+            self.link = Some(link);
+            return false;
+        }
+        self.link = Some(&mut link.as_mut().unwrap().next);
+        self.index += 1;
+        true
+    }
+
+    // Insert above the current item, the new item becomes current.
+    fn insert(&mut self, val: T) {
+        let link = self.link.as_mut().unwrap();
+        let next = link.take();
+        **link = Some(Box::new(StackNode { val, next }));
+        *self.size += 1;
+    }
+
+    // Remove the current item, the one below becomes current.
+    fn remove_current(&mut self) -> Option<T> {
+        let link = self.link.as_mut().unwrap();
+        let node = *link.take()?;
+        **link = node.next;
+        *self.size -= 1;
+        Some(node.val)
+    }
+}
+
+// LINKED QUEUE.
+
+struct QueueNode<T> {
+    val: T,
+    next: *mut QueueNode<T>,
+}
+
+/// Singly linked queue. Items are dequeued at the head and enqueued after
+/// the tail, which is kept as a pointer.
+struct LinkedQueue<T> {
+    head: *mut QueueNode<T>,
+    tail: *mut QueueNode<T>,
+    size: usize,
+    // Owns the nodes.
+    marker: PhantomData<Box<QueueNode<T>>>,
+}
+
+impl<T> LinkedQueue<T> {
+    fn new() -> Self {
+        Self { head: ptr::null_mut(), tail: ptr::null_mut(), size: 0, marker: PhantomData }
+    }
+
+    fn clear(&mut self) {
+        while self.dequeue_node().is_some() {}
+    }
+
+    fn dequeue_node(&mut self) -> Option<T> {
+        if self.head.is_null() {
+            return None;
+        }
+        let node = unsafe { Box::from_raw(self.head) };
+        self.head = node.next;
+        if self.head.is_null() {
+            self.tail = ptr::null_mut();
+        }
+        self.size -= 1;
+        Some(node.val)
+    }
+
+    // The next item out.
+    fn peek(&self) -> Option<&T> {
+        unsafe { self.head.as_ref().map(|node| &node.val) }
+    }
+
+    // Oldest to newest.
+    fn iter(&self) -> QueueIter<T> {
+        QueueIter { next: self.head, marker: PhantomData }
+    }
+
+    // A cursor on the oldest item.
+    fn cursor_mut(&mut self) -> QueueCursorMut<T> {
+        QueueCursorMut { prev: ptr::null_mut(), index: 0, queue: self }
+    }
+}
+
+impl<T> Fifo<T> for LinkedQueue<T> {
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        let node = Box::into_raw(Box::new(QueueNode { val, next: ptr::null_mut() }));
+        if self.tail.is_null() {
+            self.head = node;
+        } else {
+            unsafe { (*self.tail).next = node; }
+        }
+        self.tail = node;
+        self.size += 1;
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<T> { self.dequeue_node() }
+}
+
+impl<T> Container for LinkedQueue<T> {
+    fn len(&self) -> usize { self.size }
+    fn clear(&mut self) { LinkedQueue::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Drop for LinkedQueue<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+struct QueueIter<'a, T: 'a> {
+    next: *mut QueueNode<T>,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for QueueIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            self.next.as_ref().map(|node| {
+                self.next = node.next;
+                &node.val
+            })
+        }
+    }
+}
+
+/// Points at an item of a `LinkedQueue`, or past the newest one. It keeps
+/// the node before the current one, null at the head.
+struct QueueCursorMut<'a, T: 'a> {
+    queue: &'a mut LinkedQueue<T>,
+    prev: *mut QueueNode<T>,
+    index: usize,
+}
+
+impl<'a, T> QueueCursorMut<'a, T> {
+    // Position from the oldest item, equal to the length past the newest.
+    fn index(&self) -> usize { self.index }
+
+    fn current_ptr(&self) -> *mut QueueNode<T> {
+        if self.prev.is_null() { self.queue.head } else { unsafe { (*self.prev).next } }
+    }
+
+    fn current(&mut self) -> Option<&mut T> {
+        unsafe { self.current_ptr().as_mut().map(|node| &mut node.val) }
+    }
+
+    // Move one item towards the newest. False when already past it.
+    fn move_next(&mut self) -> bool {
+        let current = self.current_ptr();
+        if current.is_null() {
+            return false;
+        }
+        self.prev = current;
+        self.index += 1;
+        true
+    }
+
+    // Insert before the current item, the new item becomes current. Past
+    // the newest item this is an enqueue.
+    fn insert(&mut self, val: T) {
+        let current = self.current_ptr();
+        let node = Box::into_raw(Box::new(QueueNode { val, next: current }));
+        if self.prev.is_null() {
+            self.queue.head = node;
+        } else {
+            unsafe { (*self.prev).next = node; }
+        }
+        if current.is_null() {
+            self.queue.tail = node;
+        }
+        self.queue.size += 1;
+    }
+
+    // Remove the current item, the next one becomes current.
+    fn remove_current(&mut self) -> Option<T> {
+        let current = self.current_ptr();
+        if current.is_null() {
+            return None;
+        }
+        let node = unsafe { Box::from_raw(current) };
+        if self.prev.is_null() {
+            self.queue.head = node.next;
+        } else {
+            unsafe { (*self.prev).next = node.next; }
+        }
+        if node.next.is_null() {
+            self.queue.tail = self.prev;
+        }
+        self.queue.size -= 1;
+        Some(node.val)
+    }
+}
+
+// LINKED DEQUE.
+
+type DequeLink<T> = Option<NonNull<DequeNode<T>>>;
+
+struct DequeNode<T> {
+    val: T,
+    prev: DequeLink<T>, // Towards the front
+    next: DequeLink<T>, // Towards the back
+}
+
+/// Doubly linked deque, the head is the front and the tail the back.
+struct LinkedDeque<T> {
+    head: DequeLink<T>,
+    tail: DequeLink<T>,
+    size: usize,
+    marker: PhantomData<Box<DequeNode<T>>>,
+}
+
+impl<T> LinkedDeque<T> {
+    fn new() -> Self {
+        Self { head: None, tail: None, size: 0, marker: PhantomData }
+    }
+
+    fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    fn peek_front(&self) -> Option<&T> {
+        self.head.map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    fn peek_back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).val })
+    }
+
+    // Front to back, reversible.
+    fn iter(&self) -> DequeIter<T> {
+        DequeIter { front: self.head, back: self.tail, left: self.size, marker: PhantomData }
+    }
+
+    // A cursor on the front item.
+    fn cursor_front_mut(&mut self) -> DequeCursorMut<T> {
+        DequeCursorMut { current: self.head, index: 0, deque: self }
+    }
+
+    // A cursor on the back item.
+    fn cursor_back_mut(&mut self) -> DequeCursorMut<T> {
+        let index = self.size.saturating_sub(1);
+        DequeCursorMut { current: self.tail, index, deque: self }
+    }
+
+    // Link a new node between `prev` and `next`, either may be the end.
+    fn link(&mut self, val: T, prev: DequeLink<T>, next: DequeLink<T>) -> NonNull<DequeNode<T>> {
+        let node = Box::into_raw(Box::new(DequeNode { val, prev, next }));
+        let node = unsafe { NonNull::new_unchecked(node) };
+        match prev {
+            Some(prev) => unsafe { (*prev.as_ptr()).next = Some(node) },
+            None => self.head = Some(node),
+        }
+        match next {
+            Some(next) => unsafe { (*next.as_ptr()).prev = Some(node) },
+            None => self.tail = Some(node),
+        }
+        self.size += 1;
+        node
+    }
+
+    fn unlink(&mut self, node: NonNull<DequeNode<T>>) -> T {
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        match node.prev {
+            Some(prev) => unsafe { (*prev.as_ptr()).next = node.next },
+            None => self.head = node.next,
+        }
+        match node.next {
+            Some(next) => unsafe { (*next.as_ptr()).prev = node.prev },
+            None => self.tail = node.prev,
+        }
+        self.size -= 1;
+        node.val
+    }
+}
+
+impl<T> DoubleEnded<T> for LinkedDeque<T> {
+    fn push_front(&mut self, val: T) -> Result<(), String> {
+        let head = self.head;
+        self.link(val, None, head);
+        Ok(())
+    }
+
+    fn push_back(&mut self, val: T) -> Result<(), String> {
+        let tail = self.tail;
+        self.link(val, tail, None);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.head.map(|node| self.unlink(node))
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        self.tail.map(|node| self.unlink(node))
+    }
+}
+
+impl<T> Container for LinkedDeque<T> {
+    fn len(&self) -> usize { self.size }
+    fn clear(&mut self) { LinkedDeque::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Drop for LinkedDeque<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+struct DequeIter<'a, T: 'a> {
+    front: DequeLink<T>,
+    back: DequeLink<T>,
+    left: usize,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for DequeIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        self.front.map(|node| unsafe {
+            let node = &*node.as_ptr();
+            self.left -= 1;
+            self.front = node.next;
+            &node.val
+        })
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for DequeIter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        self.back.map(|node| unsafe {
+            let node = &*node.as_ptr();
+            self.left -= 1;
+            self.back = node.prev;
+            &node.val
+        })
+    }
+}
+
+/// Points at an item of a `LinkedDeque`, or at the "ghost" position
+/// between the back and the front, where `current` is None.
+struct DequeCursorMut<'a, T: 'a> {
+    deque: &'a mut LinkedDeque<T>,
+    current: DequeLink<T>,
+    // Position from the front, equal to the length on the ghost.
+    index: usize,
+}
+
+impl<'a, T> DequeCursorMut<'a, T> {
+    fn index(&self) -> Option<usize> {
+        self.current.map(|_| self.index)
+    }
+
+    fn current(&mut self) -> Option<&mut T> {
+        self.current.map(|node| unsafe { &mut (*node.as_ptr()).val })
+    }
+
+    // Towards the back. From the back to the ghost, from the ghost to the
+    // front.
+    fn move_next(&mut self) {
+        match self.current {
+            Some(node) => {
+                self.current = unsafe { (*node.as_ptr()).next };
+                self.index += 1;
+            }
+            None => {
+                self.current = self.deque.head;
+                self.index = 0;
+            }
+        }
+    }
+
+    // Towards the front, wrapping through the ghost the same way.
+    fn move_prev(&mut self) {
+        match self.current {
+            Some(node) => {
+                self.current = unsafe { (*node.as_ptr()).prev };
+                self.index = match self.current {
+                    Some(_) => self.index - 1,
+                    None => self.deque.size,
+                };
+            }
+            None => {
+                self.current = self.deque.tail;
+                self.index = self.deque.size.saturating_sub(1);
+            }
+        }
+    }
+
+    // Insert on the front side of the current item. On the ghost that is
+    // the new back.
+    fn insert_before(&mut self, val: T) {
+        let (prev, next) = match self.current {
+            Some(node) => (unsafe { (*node.as_ptr()).prev }, Some(node)),
+            None => (self.deque.tail, None),
+        };
+        self.deque.link(val, prev, next);
+        self.index += 1;
+    }
+
+    // Insert on the back side of the current item. On the ghost that is
+    // the new front.
+    fn insert_after(&mut self, val: T) {
+        let (prev, next) = match self.current {
+            Some(node) => (Some(node), unsafe { (*node.as_ptr()).next }),
+            None => (None, self.deque.head),
+        };
+        self.deque.link(val, prev, next);
+        if self.current.is_none() {
+            self.index += 1;
+        }
+    }
+
+    // Remove the current item, the cursor moves to the next one.
+    fn remove_current(&mut self) -> Option<T> {
+        let node = self.current?;
+        self.current = unsafe { (*node.as_ptr()).next };
+        Some(self.deque.unlink(node))
+    }
+}
+
+// GENERIC CODE.
+
+// Undo the last `n` edits of an editor history.
+fn undo<S: Lifo<String>>(history: &mut S, n: usize) -> Vec<String> {
+    (0..n).filter_map(|_| history.pop()).collect()
+}
+
+// Hand out jobs in rounds of `batch`.
+fn rounds<Q: Fifo<u32>>(jobs: &mut Q, batch: usize) -> Vec<Vec<u32>> {
+    let mut out = Vec::new();
+    while !jobs.is_empty() {
+        out.push((0..batch).filter_map(|_| jobs.dequeue()).collect());
+    }
+    out
+}
+
+fn is_palindrome<D: DoubleEnded<char>>(mut deque: D, word: &str) -> bool {
+    for c in word.chars() {
+        deque.push_back(c).unwrap();
+    }
+    while deque.len() > 1 {
+        if deque.pop_front() != deque.pop_back() {
+            return false;
+        }
+    }
+    true
+}
+
+fn main() {
+    trait_ops();
+    cursor_ops();
+    check_ops();
+    if !cfg!(miri) {
+        bench_ops();
+    }
+
+    fn trait_ops() {
+        println!("SAME CODE, BOTH BACKINGS");
+        let edits = ["type a", "type b", "delete", "paste"];
+        let mut vec_history = Stack::new();
+        let mut linked_history = LinkedStack::new();
+        for edit in edits.iter() {
+            vec_history.push(edit.to_string()).unwrap();
+            linked_history.push(edit.to_string()).unwrap();
+        }
+        println!("undo 2: {:?} / {:?}", undo(&mut vec_history, 2), undo(&mut linked_history, 2));
+
+        let mut vec_jobs = Queue::new(8);
+        let mut linked_jobs = LinkedQueue::new();
+        for job in 1..=5 {
+            vec_jobs.enqueue(job).unwrap();
+            linked_jobs.enqueue(job).unwrap();
+        }
+        println!("rounds of 2: {:?} / {:?}", rounds(&mut vec_jobs, 2), rounds(&mut linked_jobs, 2));
+
+        for word in ["rustsur", "deque"] {
+            println!("{word} palindrome: {} / {}", is_palindrome(Deque::new(16), word), is_palindrome(LinkedDeque::new(), word));
+        }
+        println!("=========================");
+    }
+
+    fn cursor_ops() {
+        println!("CURSORS");
+        let mut stack = LinkedStack::new();
+        for x in [4, 3, 1] {
+            stack.push(x).unwrap();
+        }
+        {
+            // Insert the missing 2 under the top, then drop the 4.
+            let mut cursor = stack.cursor_mut();
+            cursor.move_next();
+            cursor.insert(2);
+            while cursor.move_next() {}
+            println!("past the bottom at index {}", cursor.index());
+        }
+        println!("stack top to bottom: {:?}", stack.iter().collect::<Vec<_>>());
+
+        let mut queue = LinkedQueue::new();
+        for job in ["build", "flaky test", "deploy"] {
+            queue.enqueue(job).unwrap();
+        }
+        {
+            let mut cursor = queue.cursor_mut();
+            cursor.move_next();
+            println!("dropping {:?}", cursor.remove_current());
+            cursor.insert("lint");
+            cursor.move_next();
+            cursor.move_next();
+            cursor.insert("notify");
+        }
+        println!("queue oldest first: {:?}, peek {:?}", queue.iter().collect::<Vec<_>>(), queue.peek());
+
+        let mut deque = LinkedDeque::new();
+        for x in 1..=5 {
+            deque.push_back(x).unwrap();
+        }
+        {
+            let mut cursor = deque.cursor_front_mut();
+            while let Some(x) = cursor.current() {
+                if *x % 2 == 0 {
+                    cursor.remove_current();
+                } else {
+                    *x *= 10;
+                    cursor.move_next();
+                }
+            }
+            // On the ghost: before is the back, after is the front.
+            cursor.insert_before(99);
+            cursor.insert_after(0);
+        }
+        println!("deque front to back: {:?}", deque.iter().collect::<Vec<_>>());
+        println!("back to front: {:?}", deque.iter().rev().collect::<Vec<_>>());
+        println!("=========================");
+    }
+
+    fn check_ops() {
+        println!("CHECKS");
+        // Items count their drops: every item must be dropped exactly once
+        // whether it is popped, removed by a cursor or left in the list.
+        #[derive(Debug)]
+        struct Tracked(u32, Rc<Cell<usize>>);
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+        let drops = Rc::new(Cell::new(0));
+        let item = |x: u32| Tracked(x, Rc::clone(&drops));
+
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        let mut next = move |n: u64| {
+            seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17;
+            seed % n
+        };
+        let mut created = 0;
+        for _ in 0..20 {
+            // The deque against a Vec used as the model, front first.
+            let mut deque = LinkedDeque::new();
+            let mut model: Vec<u32> = Vec::new();
+            for _ in 0..40 {
+                let x = next(1000) as u32;
+                match next(6) {
+                    0 => { deque.push_front(item(x)).unwrap(); model.insert(0, x); created += 1; }
+                    1 => { deque.push_back(item(x)).unwrap(); model.push(x); created += 1; }
+                    2 => assert_eq!(deque.pop_front().map(|t| t.0), if model.is_empty() { None } else { Some(model.remove(0)) }),
+                    3 => assert_eq!(deque.pop_back().map(|t| t.0), model.pop()),
+                    _ => {
+                        // A cursor edit at a random position.
+                        let at = next(model.len() as u64 + 1) as usize;
+                        let mut cursor = deque.cursor_front_mut();
+                        for _ in 0..at {
+                            cursor.move_next();
+                        }
+                        if next(2) == 0 {
+                            cursor.insert_before(item(x));
+                            model.insert(at, x);
+                            created += 1;
+                        } else if at < model.len() {
+                            assert_eq!(cursor.remove_current().map(|t| t.0), Some(model.remove(at)));
+                        }
+                    }
+                }
+                assert_eq!(deque.iter().map(|t| t.0).collect::<Vec<_>>(), model);
+                assert_eq!(deque.iter().rev().map(|t| t.0).collect::<Vec<_>>(), model.iter().rev().cloned().collect::<Vec<_>>());
+                assert_eq!(deque.len(), model.len());
+            }
+
+            // The queue, with cursor inserts and removals at random spots.
+            let mut queue = LinkedQueue::new();
+            let mut model: Vec<u32> = Vec::new();
+            for _ in 0..40 {
+                let x = next(1000) as u32;
+                match next(4) {
+                    0 | 1 => { queue.enqueue(item(x)).unwrap(); model.push(x); created += 1; }
+                    2 => assert_eq!(queue.dequeue().map(|t| t.0), if model.is_empty() { None } else { Some(model.remove(0)) }),
+                    _ => {
+                        let at = next(model.len() as u64 + 1) as usize;
+                        let mut cursor = queue.cursor_mut();
+                        for _ in 0..at {
+                            cursor.move_next();
+                        }
+                        if next(2) == 0 {
+                            cursor.insert(item(x));
+                            model.insert(at, x);
+                            created += 1;
+                        } else if at < model.len() {
+                            assert_eq!(cursor.remove_current().map(|t| t.0), Some(model.remove(at)));
+                        }
+                    }
+                }
+                // Enqueue must still find the tail after cursor edits.
+                assert_eq!(queue.iter().map(|t| t.0).collect::<Vec<_>>(), model);
+                assert_eq!(queue.len(), model.len());
+            }
+
+            let mut stack = LinkedStack::new();
+            for x in 0..10 {
+                stack.push(item(x)).unwrap();
+                created += 1;
+            }
+            {
+                let mut cursor = stack.cursor_mut();
+                cursor.move_next();
+                cursor.remove_current();
+                cursor.insert(item(100));
+                created += 1;
+            }
+            assert_eq!(stack.iter().map(|t| t.0).take(3).collect::<Vec<_>>(), vec![9, 100, 7]);
+            assert_eq!(stack.len(), 10);
+        }
+        assert_eq!(drops.get(), created);
+        println!("{} items created and dropped exactly once", created);
+
+        // A long list must not overflow the stack when dropped.
+        let mut long = LinkedStack::new();
+        for x in 0..if cfg!(miri) { 1_000 } else { 1_000_000 } {
+            long.push(x).unwrap();
+        }
+        drop(long);
+        println!("=========================");
+    }
+
+    fn bench_ops() {
+        println!("BENCHMARK");
+        fn fifo<Q: Fifo<u64>>(mut q: Q, n: u64) -> u128 {
+            let start = Instant::now();
+            for i in 0..n {
+                q.enqueue(i).unwrap();
+            }
+            while q.dequeue().is_some() {}
+            start.elapsed().as_micros()
+        }
+        fn double_ended<D: DoubleEnded<u64>>(mut d: D, n: u64) -> u128 {
+            let start = Instant::now();
+            for i in 0..n {
+                d.push_front(i).unwrap();
+            }
+            while d.pop_front().is_some() {}
+            start.elapsed().as_micros()
+        }
+        let n = 50_000;
+        println!("{n} enqueues and dequeues: Queue {} us, LinkedQueue {} us",
+                 fifo(Queue::new(n as usize), n), fifo(LinkedQueue::new(), n));
+        println!("{n} push_front and pop_front: Deque {} us, LinkedDeque {} us",
+                 double_ended(Deque::new(n as usize), n), double_ended(LinkedDeque::new(), n));
+        println!("=========================");
+    }
+}
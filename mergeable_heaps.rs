@@ -0,0 +1,481 @@
+//! Mergeable priority queues for graph algorithms.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// Two min-priority queues tuned for decrease-key, as used by Dijkstra and
+/// Prim, behind the common `MergeablePriorityQueue` trait.
+///
+/// `IndexedDaryHeap` is keyed by small integers such as node ids. Every node
+/// has d children instead of two, which makes the heap shallower, so
+/// decrease-key (a sift up) gets cheaper while pop gets dearer.
+///
+/// `PairingHeap` is a tree where decrease-key just cuts the node out and
+/// links it back to the root, with the tidying deferred to `pop`.
+///
+/// Build with `rustc -O mergeable_heaps.rs` for meaningful benchmark numbers.
+use std::time::Instant;
+
+/// Min-priority queue with decrease-key and melding.
+trait MergeablePriorityQueue {
+    type Item;
+    type Prio: Ord;
+    type Handle: Copy;
+
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn push(&mut self, val: Self::Item, prio: Self::Prio) -> Self::Handle;
+    // Remove the item with the lowest priority.
+    fn pop(&mut self) -> Option<(Self::Item, Self::Prio)>;
+    fn peek(&self) -> Option<(&Self::Item, &Self::Prio)>;
+    // Lower the priority behind a handle. Raising it is an error.
+    fn decrease_key(&mut self, handle: Self::Handle, prio: Self::Prio) -> Result<(), String>;
+    // Move every item of `other` into this queue.
+    fn meld(&mut self, other: Self);
+}
+
+// INDEXED D-ARY HEAP.
+
+// Marks a key that is not in the heap.
+const ABSENT: usize = usize::MAX;
+
+#[derive(Debug)]
+struct IndexedDaryHeap<P: Ord> {
+    d: usize,
+    // The heap, holding keys. Children of index i are d*i + 1 ..= d*i + d.
+    heap: Vec<usize>,
+    // Priority and heap index of every key, indexed by key.
+    prios: Vec<Option<P>>,
+    pos: Vec<usize>,
+}
+
+impl<P: Ord> IndexedDaryHeap<P> {
+    // Heap of arity `d`, keys below `keys` need no reallocation.
+    fn new(d: usize, keys: usize) -> Self {
+        assert!(d >= 2, "a d-ary heap needs d >= 2");
+        Self {
+            d,
+            heap: Vec::with_capacity(keys),
+            prios: Vec::with_capacity(keys),
+            pos: Vec::with_capacity(keys),
+        }
+    }
+
+    fn contains(&self, key: usize) -> bool {
+        key < self.pos.len() && self.pos[key] != ABSENT
+    }
+
+    fn prio(&self, key: usize) -> Option<&P> {
+        self.prios.get(key).and_then(|p| p.as_ref())
+    }
+
+    fn less(&self, a: usize, b: usize) -> bool {
+        self.prios[self.heap[a]] < self.prios[self.heap[b]]
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.pos[self.heap[a]] = a;
+        self.pos[self.heap[b]] = b;
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / self.d;
+            if !self.less(index, parent) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let first = self.d * index + 1;
+            let last = (first + self.d).min(self.heap.len());
+            let mut top = index;
+            for child in first..last {
+                if self.less(child, top) {
+                    top = child;
+                }
+            }
+            if top == index {
+                break;
+            }
+            self.swap(index, top);
+            index = top;
+        }
+    }
+}
+
+impl<P: Ord> MergeablePriorityQueue for IndexedDaryHeap<P> {
+    type Item = usize;
+    type Prio = P;
+    // The key is its own handle.
+    type Handle = usize;
+
+    fn len(&self) -> usize { self.heap.len() }
+
+    // Pushing a key that is already in the heap keeps the lower priority.
+    fn push(&mut self, key: usize, prio: P) -> usize {
+        if self.contains(key) {
+            let _ = self.decrease_key(key, prio);
+            return key;
+        }
+        if key >= self.pos.len() {
+            self.pos.resize(key + 1, ABSENT);
+            self.prios.resize_with(key + 1, || None);
+        }
+        self.prios[key] = Some(prio);
+        self.pos[key] = self.heap.len();
+        self.heap.push(key);
+        self.sift_up(self.heap.len() - 1);
+        key
+    }
+
+    fn pop(&mut self) -> Option<(usize, P)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let last = self.heap.len() - 1;
+        self.swap(0, last);
+        let key = self.heap.pop().unwrap();
+        self.pos[key] = ABSENT;
+        if !self.heap.is_empty() {
+            self.sift_down(0);
+        }
+        Some((key, self.prios[key].take().unwrap()))
+    }
+
+    fn peek(&self) -> Option<(&usize, &P)> {
+        self.heap.first().map(|key| (key, self.prios[*key].as_ref().unwrap()))
+    }
+
+    fn decrease_key(&mut self, key: usize, prio: P) -> Result<(), String> {
+        if !self.contains(key) {
+            return Err("Key is not in the heap".to_string());
+        }
+        if Some(&prio) > self.prios[key].as_ref() {
+            return Err("New priority is greater than the current one".to_string());
+        }
+        self.prios[key] = Some(prio);
+        self.sift_up(self.pos[key]);
+        Ok(())
+    }
+
+    // Keys in both heaps keep the lower of their two priorities.
+    fn meld(&mut self, mut other: Self) {
+        while let Some((key, prio)) = other.pop() {
+            self.push(key, prio);
+        }
+    }
+}
+
+// PAIRING HEAP.
+
+const NIL: usize = usize::MAX;
+
+#[derive(Debug)]
+struct PairNode<T, P> {
+    // Item and priority, taken out when the node is popped.
+    item: Option<(T, P)>,
+    child: usize,
+    sibling: usize,
+    // Parent for a first child, left sibling otherwise.
+    prev: usize,
+}
+
+/// Nodes live in an arena and refer to each other by index. Popped nodes
+/// are not reused, so a handle never points at the wrong item.
+#[derive(Debug)]
+struct PairingHeap<T, P: Ord> {
+    nodes: Vec<PairNode<T, P>>,
+    root: usize,
+    size: usize,
+}
+
+impl<T, P: Ord> PairingHeap<T, P> {
+    fn new() -> Self {
+        Self { nodes: Vec::new(), root: NIL, size: 0 }
+    }
+
+    fn get(&self, handle: usize) -> Option<(&T, &P)> {
+        let node = self.nodes.get(handle)?;
+        node.item.as_ref().map(|(val, prio)| (val, prio))
+    }
+
+    // Priority of a node still in the heap.
+    fn prio(&self, node: usize) -> &P {
+        &self.nodes[node].item.as_ref().unwrap().1
+    }
+
+    // Make the root with the larger priority the first child of the other.
+    fn link(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL { return b; }
+        if b == NIL { return a; }
+        let (parent, child) = if self.prio(b) < self.prio(a) { (b, a) } else { (a, b) };
+        let first = self.nodes[parent].child;
+        self.nodes[child].sibling = first;
+        self.nodes[child].prev = parent;
+        if first != NIL {
+            self.nodes[first].prev = child;
+        }
+        self.nodes[parent].child = child;
+        parent
+    }
+
+    // Two-pass merge of a sibling list: link pairs left to right, then
+    // fold the results right to left.
+    fn merge_pairs(&mut self, first: usize) -> usize {
+        let mut roots = Vec::new();
+        let mut cur = first;
+        while cur != NIL {
+            let a = cur;
+            let b = self.nodes[a].sibling;
+            cur = if b == NIL { NIL } else { self.nodes[b].sibling };
+            self.detach(a);
+            if b != NIL {
+                self.detach(b);
+            }
+            roots.push(self.link(a, b));
+        }
+        let mut root = NIL;
+        while let Some(next) = roots.pop() {
+            root = self.link(next, root);
+        }
+        root
+    }
+
+    fn detach(&mut self, node: usize) {
+        self.nodes[node].sibling = NIL;
+        self.nodes[node].prev = NIL;
+    }
+}
+
+impl<T, P: Ord> MergeablePriorityQueue for PairingHeap<T, P> {
+    type Item = T;
+    type Prio = P;
+    type Handle = usize;
+
+    fn len(&self) -> usize { self.size }
+
+    fn push(&mut self, val: T, prio: P) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(PairNode { item: Some((val, prio)), child: NIL, sibling: NIL, prev: NIL });
+        self.root = self.link(self.root, node);
+        self.size += 1;
+        node
+    }
+
+    fn pop(&mut self) -> Option<(T, P)> {
+        if self.root == NIL {
+            return None;
+        }
+        let old = self.root;
+        self.root = self.merge_pairs(self.nodes[old].child);
+        self.nodes[old].child = NIL;
+        self.size -= 1;
+        self.nodes[old].item.take()
+    }
+
+    fn peek(&self) -> Option<(&T, &P)> {
+        if self.root == NIL {
+            return None;
+        }
+        self.get(self.root)
+    }
+
+    // Cut the node out of its parent's child list and link it to the root.
+    fn decrease_key(&mut self, node: usize, prio: P) -> Result<(), String> {
+        if self.get(node).is_none() {
+            return Err("Handle is not in the heap".to_string());
+        }
+        if &prio > self.prio(node) {
+            return Err("New priority is greater than the current one".to_string());
+        }
+        self.nodes[node].item.as_mut().unwrap().1 = prio;
+        if node == self.root {
+            return Ok(());
+        }
+        let prev = self.nodes[node].prev;
+        let sibling = self.nodes[node].sibling;
+        if self.nodes[prev].child == node {
+            self.nodes[prev].child = sibling;
+        } else {
+            self.nodes[prev].sibling = sibling;
+        }
+        if sibling != NIL {
+            self.nodes[sibling].prev = prev;
+        }
+        self.detach(node);
+        self.root = self.link(self.root, node);
+        Ok(())
+    }
+
+    // O(1) apart from moving the arena. Handles from `other` are not valid
+    // afterwards.
+    fn meld(&mut self, other: Self) {
+        let offset = self.nodes.len();
+        let shift = |i: usize| if i == NIL { NIL } else { i + offset };
+        for mut node in other.nodes {
+            node.child = shift(node.child);
+            node.sibling = shift(node.sibling);
+            node.prev = shift(node.prev);
+            self.nodes.push(node);
+        }
+        self.root = self.link(self.root, shift(other.root));
+        self.size += other.size;
+    }
+}
+
+// Small xorshift generator, enough for benchmark workloads.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 { self.next() % n }
+}
+
+// Dijkstra over an adjacency list, written once for any of the queues.
+fn dijkstra<Q>(adj: &[Vec<(usize, u64)>], source: usize, mut queue: Q) -> Vec<u64>
+where
+    Q: MergeablePriorityQueue<Item = usize, Prio = u64>,
+{
+    let n = adj.len();
+    let mut dist = vec![u64::MAX; n];
+    let mut handles: Vec<Option<Q::Handle>> = vec![None; n];
+    let mut done = vec![false; n];
+    dist[source] = 0;
+    handles[source] = Some(queue.push(source, 0));
+    while let Some((node, d)) = queue.pop() {
+        done[node] = true;
+        for &(to, w) in adj[node].iter() {
+            let nd = d + w;
+            if done[to] || nd >= dist[to] {
+                continue;
+            }
+            dist[to] = nd;
+            match handles[to] {
+                Some(h) => queue.decrease_key(h, nd).unwrap(),
+                None => handles[to] = Some(queue.push(to, nd)),
+            }
+        }
+    }
+    dist
+}
+
+fn main() {
+    basic_ops();
+    meld_ops();
+    bench_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let mut d = IndexedDaryHeap::new(4, 8);
+        let mut p = PairingHeap::new();
+        let mut handles = Vec::new();
+        for (key, prio) in [(0, 50), (1, 20), (2, 40), (3, 10), (4, 30)] {
+            d.push(key, prio);
+            handles.push(p.push(key, prio));
+        }
+        d.decrease_key(2, 5).unwrap();
+        p.decrease_key(handles[2], 5).unwrap();
+        println!("raise key: {:?}", d.decrease_key(0, 99));
+        let mut d_order = Vec::new();
+        while let Some(item) = d.pop() { d_order.push(item); }
+        let mut p_order = Vec::new();
+        while let Some(item) = p.pop() { p_order.push(item); }
+        println!("4-ary:   {:?}", d_order);
+        println!("pairing: {:?}", p_order);
+        println!("=========================");
+    }
+
+    fn meld_ops() {
+        println!("MELD");
+        let mut a = PairingHeap::new();
+        let mut b = PairingHeap::new();
+        for prio in [7, 3, 9] { a.push("a", prio); }
+        for prio in [8, 1, 4] { b.push("b", prio); }
+        a.meld(b);
+        print!("pairing: ");
+        while let Some((val, prio)) = a.pop() { print!("{val}:{prio}, "); }
+        let mut x = IndexedDaryHeap::new(2, 4);
+        let mut y = IndexedDaryHeap::new(2, 4);
+        x.push(0, 10); x.push(1, 5);
+        y.push(1, 2); y.push(2, 7);
+        x.meld(y);
+        print!("\nbinary: ");
+        while let Some((key, prio)) = x.pop() { print!("{key}:{prio}, "); }
+        println!("\n=========================");
+    }
+
+    fn bench_ops() {
+        println!("RANDOM DECREASE-KEY WORKLOAD");
+        let n = 200_000;
+        let ops = 1_000_000;
+
+        // Push n items, then mix random decrease-keys with the odd pop.
+        fn workload<Q: MergeablePriorityQueue<Item = usize, Prio = u64>>(mut q: Q, n: usize, ops: usize) -> u64 {
+            let mut rng = Rng(0x2545F4914F6CDD1D);
+            let mut prios = vec![0u64; n];
+            let mut handles = Vec::with_capacity(n);
+            for key in 0..n {
+                prios[key] = 1_000_000_000 + rng.below(1_000_000_000);
+                handles.push(q.push(key, prios[key]));
+            }
+            let mut alive = vec![true; n];
+            let mut checksum = 0;
+            for i in 0..ops {
+                if i % 10 == 0 {
+                    if let Some((key, prio)) = q.pop() {
+                        alive[key] = false;
+                        checksum += prio;
+                    }
+                } else {
+                    let key = rng.below(n as u64) as usize;
+                    if alive[key] {
+                        prios[key] -= rng.below(prios[key] / 100 + 1);
+                        q.decrease_key(handles[key], prios[key]).unwrap();
+                    }
+                }
+            }
+            checksum
+        }
+
+        let mut results = Vec::new();
+        for &d in [2, 4, 8].iter() {
+            let start = Instant::now();
+            let sum = workload(IndexedDaryHeap::new(d, n), n, ops);
+            results.push(sum);
+            println!("{}-ary heap     {:>8.1} ms", d, start.elapsed().as_secs_f64() * 1000.0);
+        }
+        let start = Instant::now();
+        let sum = workload(PairingHeap::new(), n, ops);
+        results.push(sum);
+        println!("pairing heap   {:>8.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        assert!(results.windows(2).all(|w| w[0] == w[1]), "heaps disagree");
+
+        // Dijkstra on a random sparse graph, all heaps must agree.
+        let nodes = 100_000;
+        let mut rng = Rng(88172645463325252);
+        let mut adj = vec![Vec::new(); nodes];
+        for from in 0..nodes {
+            for _ in 0..5 {
+                let to = rng.below(nodes as u64) as usize;
+                adj[from].push((to, 1 + rng.below(100)));
+            }
+        }
+        let start = Instant::now();
+        let by_dary = dijkstra(&adj, 0, IndexedDaryHeap::new(4, nodes));
+        println!("dijkstra 4-ary {:>8.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        let start = Instant::now();
+        let by_pairing = dijkstra(&adj, 0, PairingHeap::new());
+        println!("dijkstra pair  {:>8.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        assert_eq!(by_dary, by_pairing);
+        println!("=========================");
+    }
+}
@@ -0,0 +1,292 @@
+//! Min-max heap.
+#![allow(warnings)]
+/// A double-ended priority queue: both the smallest and the largest item
+/// can be looked at in O(1) and removed in O(log n).
+/// Like the `Deque<T>` of deckrus.rs it holds at most `cap` items. When
+/// full, `push` fails, while `push_evict` makes room by dropping the
+/// current minimum, which suits a scheduler keeping only its best jobs.
+///
+/// The heap is stored in `data` like a binary heap, but the levels
+/// alternate: an item on an even level (the root is level 0) is the
+/// smallest of its subtree, an item on an odd level the largest.
+
+#[derive(Debug)]
+struct MinMaxHeap<T> {
+    cap: usize,
+    data: Vec<T>,
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    fn new(cap: usize) -> Self {
+        Self {
+            cap: cap,
+            data: Vec::with_capacity(cap),
+        }
+    }
+
+    fn len(&self) -> usize { self.data.len() }
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+
+    fn clear(&mut self) {
+        self.data = Vec::with_capacity(self.cap);
+    }
+
+    // Adding an item, O(log n).
+    fn push(&mut self, val: T) -> Result<(), String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        self.data.push(val);
+        self.bubble_up(self.len() - 1);
+        Ok(())
+    }
+
+    // Adding an item, evicting the minimum when full.
+    // Returns the evicted item, which is `val` itself when it is not
+    // larger than everything already queued.
+    fn push_evict(&mut self, val: T) -> Option<T> {
+        if !self.is_full() {
+            let _ = self.push(val);
+            return None;
+        }
+        match self.peek_min() {
+            Some(min) if *min < val => {
+                // Replace the root and let it sink to its place.
+                let evicted = std::mem::replace(&mut self.data[0], val);
+                self.trickle_down(0);
+                Some(evicted)
+            }
+            _ => Some(val),
+        }
+    }
+
+    // The smallest item, at the root.
+    fn peek_min(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    // The largest item, one of the root's children.
+    fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|index| &self.data[index])
+    }
+
+    fn pop_min(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    fn pop_max(&mut self) -> Option<T> {
+        let index = self.max_index()?;
+        Some(self.remove_at(index))
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ => Some(if self.data[1] >= self.data[2] { 1 } else { 2 }),
+        }
+    }
+
+    // Swap the last item into the hole and sink it.
+    fn remove_at(&mut self, index: usize) -> T {
+        let val = self.data.swap_remove(index);
+        if index < self.len() {
+            self.trickle_down(index);
+        }
+        val
+    }
+
+    fn is_min_level(index: usize) -> bool {
+        // Level of index i is floor(log2(i + 1)).
+        (usize::BITS - 1 - (index + 1).leading_zeros()) % 2 == 0
+    }
+
+    // Does `a` belong above `b` on a min level (or a max level)?
+    fn before(&self, a: usize, b: usize, min: bool) -> bool {
+        if min { self.data[a] < self.data[b] } else { self.data[a] > self.data[b] }
+    }
+
+    fn bubble_up(&mut self, index: usize) {
+        if index == 0 {
+            return;
+        }
+        let parent = (index - 1) / 2;
+        let min = Self::is_min_level(index);
+        // An item on a min level larger than its (max level) parent
+        // belongs on the max levels, and the other way round.
+        if self.before(parent, index, min) {
+            self.data.swap(index, parent);
+            self.bubble_up_grand(parent, !min);
+        } else {
+            self.bubble_up_grand(index, min);
+        }
+    }
+
+    // Move up two levels at a time, staying on min or on max levels.
+    fn bubble_up_grand(&mut self, mut index: usize, min: bool) {
+        while index > 2 {
+            let grand = ((index - 1) / 2 - 1) / 2;
+            if !self.before(index, grand, min) {
+                break;
+            }
+            self.data.swap(index, grand);
+            index = grand;
+        }
+    }
+
+    fn trickle_down(&mut self, mut index: usize) {
+        let min = Self::is_min_level(index);
+        loop {
+            // Best of the children and grandchildren.
+            let first_child = 2 * index + 1;
+            if first_child >= self.len() {
+                return;
+            }
+            let first_grand = 2 * first_child + 1;
+            let mut best = first_child;
+            let candidates = [first_child + 1, first_grand, first_grand + 1, first_grand + 2, first_grand + 3];
+            for &c in candidates.iter() {
+                if c < self.len() && self.before(c, best, min) {
+                    best = c;
+                }
+            }
+            if !self.before(best, index, min) {
+                return;
+            }
+            self.data.swap(best, index);
+            if best < first_grand {
+                // A child is on the other kind of level, nothing below it
+                // can be out of place.
+                return;
+            }
+            // The item that came down may be on the wrong side of the
+            // grandchild's parent.
+            let parent = (best - 1) / 2;
+            if self.before(parent, best, min) {
+                self.data.swap(parent, best);
+            }
+            index = best;
+        }
+    }
+
+    // Iter: Return an immutable iterator, in storage order.
+    fn iter(&self) -> Iter<T> {
+        let mut iterator = Iter { heap: Vec::new() };
+        for item in self.data.iter() {
+            iterator.heap.push(item);
+        }
+        iterator
+    }
+
+    // IntoIter: the heap turned into an iterator, smallest first.
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+// IntoIter
+struct IntoIter<T>(MinMaxHeap<T>);
+impl<T: Ord> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_min()
+    }
+}
+
+impl<T: Ord> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.pop_max()
+    }
+}
+
+// Iter
+struct Iter<'a, T: 'a> { heap: Vec<&'a T> }
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.heap.len() != 0 {
+            Some(self.heap.remove(0))
+        } else {
+            None
+        }
+    }
+}
+
+fn main() {
+    basic_ops();
+    scheduler_ops();
+    check_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let mut heap = MinMaxHeap::new(4);
+        heap.push(5); heap.push(1); heap.push(9); heap.push(3);
+        println!("push when full: {:?}", heap.push(7));
+        println!("min: {:?}, max: {:?}, len: {}", heap.peek_min(), heap.peek_max(), heap.len());
+        println!("pop_max: {:?}, pop_min: {:?}", heap.pop_max(), heap.pop_min());
+        println!("both ends: {:?}", heap.into_iter().rev().collect::<Vec<_>>());
+        println!("================================================");
+    }
+
+    fn scheduler_ops() {
+        println!("JOB SCHEDULER");
+        // Keep the three most important jobs, (priority, name).
+        let mut jobs = MinMaxHeap::new(3);
+        for job in [(4, "backup"), (9, "deploy"), (2, "cleanup"), (7, "report"), (1, "lint")] {
+            if let Some(evicted) = jobs.push_evict(job) {
+                println!("evicted {:?}", evicted);
+            }
+        }
+        while let Some(job) = jobs.pop_max() {
+            println!("run {:?}", job);
+        }
+        println!("================================================");
+    }
+
+    fn check_ops() {
+        println!("RANDOMISED CHECK");
+        // Compare with a sorted Vec over a random mix of operations.
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        let mut next = move || {
+            seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17;
+            seed
+        };
+        let mut heap = MinMaxHeap::new(64);
+        let mut model: Vec<u64> = Vec::new();
+        for _ in 0..100_000 {
+            let val = next() % 1000;
+            match next() % 4 {
+                0 => assert_eq!(heap.pop_min(), if model.is_empty() { None } else { Some(model.remove(0)) }),
+                1 => assert_eq!(heap.pop_max(), model.pop()),
+                2 => {
+                    let evicted = heap.push_evict(val);
+                    if model.len() < 64 {
+                        assert_eq!(evicted, None);
+                        model.push(val);
+                    } else if model[0] < val {
+                        assert_eq!(evicted, Some(model.remove(0)));
+                        model.push(val);
+                    } else {
+                        assert_eq!(evicted, Some(val));
+                    }
+                    model.sort();
+                }
+                _ => {
+                    if heap.push(val).is_ok() {
+                        model.push(val);
+                        model.sort();
+                    }
+                }
+            }
+            assert_eq!(heap.peek_min(), model.first());
+            assert_eq!(heap.peek_max(), model.last());
+        }
+        println!("100000 random operations agree with a sorted Vec");
+        println!("================================================");
+    }
+}
@@ -2,6 +2,7 @@
 #![allow(warnings)]
 /// Palindromes are strings in which characters at the same position
 /// from both the ends are the same.
+use std::collections::VecDeque;
 
 #[derive(Debug)]
 struct Deque<T> {
@@ -19,50 +20,107 @@ impl<T> Deque<T> {
     fn is_empty(&self) -> bool { self.len() == 0 }
     fn clear(&mut self) { self.data = Vec::with_capacity(self.cap) }
 
-    fn add_front(&mut self, val: T) -> Result<(), String> {
+    // `data` runs from the front to the back.
+    fn push_front(&mut self, val: T) -> Result<(), String> {
         if self.is_full() {
             return Err("No space left".to_string());
         }
-        self.data.push(val);
+        self.data.insert(0, val);
         Ok(())
     }
 
-    fn add_rear(&mut self, val: T) -> Result<(), String> {
+    fn push_back(&mut self, val: T) -> Result<(), String> {
         if self.is_full() {
             return Err("No space left".to_string());
         }
-        self.data.insert(0, val);
+        self.data.push(val);
         Ok(())
     }
 
-    fn remove_front(&mut self) -> Option<T> {
+    fn pop_front(&mut self) -> Option<T> {
         if !self.is_empty() {
-            self.data.pop()
+            Some(self.data.remove(0))
         } else {
             None
         }
     }
 
-    fn remove_rear(&mut self) -> Option<T> {
-        if !self.is_empty() {
-            Some(self.data.remove(0))
-        } else {
-            None
-        }
+    fn pop_back(&mut self) -> Option<T> {
+        self.data.pop()
     }
+
+    fn front(&self) -> Option<&T> { self.data.first() }
+    fn back(&self) -> Option<&T> { self.data.last() }
+}
+
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait DoubleEnded<T>: Container {
+    fn push_front(&mut self, val: T) -> Result<(), String>;
+    fn push_back(&mut self, val: T) -> Result<(), String>;
+    fn pop_front(&mut self) -> Option<T>;
+    fn pop_back(&mut self) -> Option<T>;
+}
+
+impl<T> Container for Deque<T> {
+    fn len(&self) -> usize { Deque::len(self) }
+    fn clear(&mut self) { Deque::clear(self) }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+impl<T> DoubleEnded<T> for Deque<T> {
+    fn push_front(&mut self, val: T) -> Result<(), String> { Deque::push_front(self, val) }
+    fn push_back(&mut self, val: T) -> Result<(), String> { Deque::push_back(self, val) }
+    fn pop_front(&mut self) -> Option<T> { Deque::pop_front(self) }
+    fn pop_back(&mut self) -> Option<T> { Deque::pop_back(self) }
+}
+
+// The standard library's ring buffer as another backing, unbounded.
+// Its front and back are ours.
+impl<T> Container for VecDeque<T> {
+    fn len(&self) -> usize { VecDeque::len(self) }
+    fn clear(&mut self) { VecDeque::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> DoubleEnded<T> for VecDeque<T> {
+    fn push_front(&mut self, val: T) -> Result<(), String> {
+        VecDeque::push_front(self, val);
+        Ok(())
+    }
+
+    fn push_back(&mut self, val: T) -> Result<(), String> {
+        VecDeque::push_back(self, val);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> { VecDeque::pop_front(self) }
+    fn pop_back(&mut self) -> Option<T> { VecDeque::pop_back(self) }
 }
 
 fn palindrome_checker(pal: &str) -> bool {
-    let mut d = Deque::new(pal.len());
+    let d = Deque::new(pal.len());
+    palindrome_checker_with(d, pal)
+}
+
+// The check on any deque, which must start empty.
+fn palindrome_checker_with<D: DoubleEnded<char>>(mut d: D, pal: &str) -> bool {
     for c in pal.chars() {
-        // Add rear so they retain their order
-        let r_ = d.add_rear(c);
+        // Add at the back so they retain their order
+        let r_ = d.push_back(c);
     }
 
     let mut is_pal = true;
     while d.len() > 1 && is_pal {
-        let head = d.remove_front();
-        let tail = d.remove_rear();
+        let head = d.pop_front();
+        let tail = d.pop_back();
         if head != tail {
             is_pal = false;
         }
@@ -78,10 +136,11 @@ fn main() {
     fn deque_ops() {
         println!("TESTING DEQUE OPERATIONS");
         let mut deque = Deque::new(4);
-        deque.add_front("Leonhard"); deque.add_rear("Euler");
-        deque.add_front("Simon"); deque.add_rear("Laplace");
+        deque.push_front("Leonhard"); deque.push_back("Euler");
+        deque.push_front("Simon"); deque.push_back("Laplace");
         println!("The deque data is {:?}", deque);
-        deque.remove_front(); deque.remove_rear();
+        println!("Front: {:?}, Back: {:?}", deque.front(), deque.back());
+        deque.pop_front(); deque.pop_back();
         println!("The deque data is {:?}", deque);
         println!("=======================================");
     }
@@ -91,6 +150,9 @@ fn main() {
         let my_str = "Greek";
         let check_pal = palindrome_checker(my_str);
         println!("The string '{my_str}' is palindrome: {check_pal}");
+        let my_str = "racecar";
+        let check_pal = palindrome_checker_with(VecDeque::new(), my_str);
+        println!("On a VecDeque, the string '{my_str}' is palindrome: {check_pal}");
         println!("==============================================");
     }
 }
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+#![allow(warnings)]
+//! Persistent stack.
+/// # Persistent stack
+/// An immutable stack where `push` and `pop` return a new version and leave
+/// the old one untouched. Versions share their common tail through `Rc`
+/// links, so both operations and `clone` are O(1) and many versions can
+/// coexist cheaply, e.g. for undo history or backtracking search.
+use std::rc::Rc;
+
+#[derive(Debug)]
+struct Node<T> {
+    val: T,
+    next: Link<T>,
+}
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+#[derive(Debug)]
+struct PersistentStack<T> {
+    // Size of the stack.
+    size: usize,
+    // Top node, shared with every version pushed on top of this one.
+    head: Link<T>,
+}
+
+impl<T> PersistentStack<T> {
+    // Initializing the stack
+    fn new() -> Self {
+        Self { size: 0, head: None }
+    }
+
+    // Check if the stack is empty.
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    // Length or number of items in the stack.
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    // New version with `val` on top, this version is unchanged.
+    fn push(&self, val: T) -> Self {
+        let node = Node { val, next: self.head.clone() };
+        Self { size: self.size + 1, head: Some(Rc::new(node)) }
+    }
+
+    // New version without the top item, None when empty.
+    // Use `peek` first to read the item being removed.
+    fn pop(&self) -> Option<Self> {
+        self.head.as_ref().map(|node| Self {
+            size: self.size - 1,
+            head: node.next.clone(),
+        })
+    }
+
+    // Returning reference to the top value
+    fn peek(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.val)
+    }
+
+    // Getting immutable stack iterator, top first.
+    fn iter(&self) -> Iter<T> {
+        Iter { next: self.head.as_deref() }
+    }
+}
+
+// Cloning only copies the head pointer, T does not need to be Clone.
+impl<T> Clone for PersistentStack<T> {
+    fn clone(&self) -> Self {
+        Self { size: self.size, head: self.head.clone() }
+    }
+}
+
+// The default drop would recurse once per node and can overflow the call
+// stack on long chains. Nodes are unlinked in a loop instead, stopping at
+// the first one still shared with another version.
+impl<T> Drop for PersistentStack<T> {
+    fn drop(&mut self) {
+        let mut head = self.head.take();
+        while let Some(node) = head {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => head = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+struct Iter<'a, T: 'a> { next: Option<&'a Node<T>> }
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.val
+        })
+    }
+}
+
+fn main() {
+    version_ops();
+    undo_ops();
+    backtracking_ops();
+
+    fn version_ops() {
+        println!("VERSIONS");
+        let empty = PersistentStack::new();
+        let a = empty.push(1).push(2);
+        let b = a.push(3);
+        let c = a.push(4);
+        let d = b.pop().unwrap();
+        println!("a: {:?}", a.iter().collect::<Vec<_>>());
+        println!("b: {:?}", b.iter().collect::<Vec<_>>());
+        println!("c: {:?}", c.iter().collect::<Vec<_>>());
+        println!("d = b.pop(): {:?}, len: {}", d.iter().collect::<Vec<_>>(), d.len());
+        println!("empty is still empty: {}", empty.is_empty());
+        println!("================================================");
+    }
+
+    fn undo_ops() {
+        println!("UNDO HISTORY");
+        // Every edit keeps the previous document version around.
+        let mut history = vec![PersistentStack::new()];
+        for word in ["Leonhard", "Euler", "Joseph", "Fourier"] {
+            let next = history.last().unwrap().push(word);
+            history.push(next);
+        }
+        history.pop(); history.pop();
+        let doc = history.last().unwrap();
+        println!("After two undos the top is {:?}, len: {}", doc.peek(), doc.len());
+
+        // A long chain is dropped without recursion.
+        let mut long = PersistentStack::new();
+        for i in 0..1_000_000 {
+            long = long.push(i);
+        }
+        drop(long);
+        println!("================================================");
+    }
+
+    fn backtracking_ops() {
+        println!("BACKTRACKING");
+        // Place 6 queens, every branch pushes onto a shared partial solution.
+        fn place(n: usize, queens: &PersistentStack<usize>, found: &mut Vec<Vec<usize>>) {
+            if queens.len() == n {
+                let mut cols: Vec<usize> = queens.iter().cloned().collect();
+                cols.reverse();
+                found.push(cols);
+                return;
+            }
+            for col in 0..n {
+                let safe = queens.iter().enumerate().all(|(dist, &c)| {
+                    c != col && c + dist + 1 != col && col + dist + 1 != c
+                });
+                if safe {
+                    place(n, &queens.push(col), found);
+                }
+            }
+        }
+        let mut found = Vec::new();
+        place(6, &PersistentStack::new(), &mut found);
+        println!("6 queens: {} solutions, first {:?}", found.len(), found[0]);
+        println!("================================================");
+    }
+}
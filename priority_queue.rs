@@ -0,0 +1,359 @@
+//! Priority queue.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// A bounded priority queue on an array-backed binary heap.
+/// The item with the highest priority comes out first, or the lowest when
+/// built with `Order::Min`. `push` hands back a `Handle` that can later
+/// change the item's priority in O(log n).
+///
+/// The heap lives in `data`: the children of index i are at 2i + 1 and
+/// 2i + 2. `slots` maps every handle to the item's current index.
+
+/// Which end of the priorities comes out first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Order {
+    Max,
+    Min,
+}
+
+/// Refers to an item in the queue. The generation makes handles of items
+/// that already left the queue stale instead of pointing at a new item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Handle {
+    id: usize,
+    gen: u64,
+}
+
+#[derive(Debug)]
+struct Entry<T, P> {
+    val: T,
+    prio: P,
+    id: usize,
+}
+
+#[derive(Debug)]
+struct Slot {
+    gen: u64,
+    // Index in `data`, None while the slot is free.
+    pos: Option<usize>,
+}
+
+#[derive(Debug)]
+struct PriorityQueue<T, P: Ord> {
+    cap: usize, // Capacity
+    order: Order,
+    data: Vec<Entry<T, P>>, // The heap
+    slots: Vec<Slot>,
+    free: Vec<usize>, // Slots ready for reuse
+}
+
+impl<T, P: Ord> PriorityQueue<T, P> {
+    // Max-heap holding at most `size` items.
+    fn new(size: usize) -> Self {
+        Self::with_order(size, Order::Max)
+    }
+
+    // Min-heap holding at most `size` items.
+    fn new_min(size: usize) -> Self {
+        Self::with_order(size, Order::Min)
+    }
+
+    fn with_order(size: usize, order: Order) -> Self {
+        Self {
+            cap: size,
+            order,
+            data: Vec::with_capacity(size),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+    fn len(&self) -> usize { self.data.len() }
+
+    fn clear(&mut self) {
+        for entry in self.data.drain(..) {
+            let slot = &mut self.slots[entry.id];
+            slot.gen += 1;
+            slot.pos = None;
+            self.free.push(entry.id);
+        }
+    }
+
+    // Add an item, O(log n).
+    fn push(&mut self, val: T, prio: P) -> Result<Handle, String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                self.slots.push(Slot { gen: 0, pos: None });
+                self.slots.len() - 1
+            }
+        };
+        let index = self.data.len();
+        self.data.push(Entry { val, prio, id });
+        self.slots[id].pos = Some(index);
+        self.sift_up(index);
+        Ok(Handle { id, gen: self.slots[id].gen })
+    }
+
+    // Remove the item with the highest priority, O(log n).
+    fn pop(&mut self) -> Option<(T, P)> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    // The item with the highest priority.
+    fn peek(&self) -> Option<(&T, &P)> {
+        self.data.first().map(|entry| (&entry.val, &entry.prio))
+    }
+
+    // The item and priority behind a handle, None if it left the queue.
+    fn get(&self, handle: Handle) -> Option<(&T, &P)> {
+        let index = self.index_of(handle)?;
+        let entry = &self.data[index];
+        Some((&entry.val, &entry.prio))
+    }
+
+    // Set a new priority, returning the old one. O(log n).
+    fn change_priority(&mut self, handle: Handle, prio: P) -> Option<P> {
+        let index = self.index_of(handle)?;
+        let old = std::mem::replace(&mut self.data[index].prio, prio);
+        self.sift_up(index);
+        self.sift_down(self.slots[handle.id].pos.unwrap());
+        Some(old)
+    }
+
+    // Lower the priority value of an item, as in Dijkstra's algorithm on a
+    // min-heap. Raising it is an error, use `change_priority` for that.
+    fn decrease_key(&mut self, handle: Handle, prio: P) -> Result<(), String> {
+        let index = match self.index_of(handle) {
+            Some(index) => index,
+            None => return Err("Stale handle".to_string()),
+        };
+        if prio > self.data[index].prio {
+            return Err("New priority is greater than the current one".to_string());
+        }
+        self.change_priority(handle, prio);
+        Ok(())
+    }
+
+    // Take an item out wherever it is in the heap.
+    fn remove(&mut self, handle: Handle) -> Option<(T, P)> {
+        let index = self.index_of(handle)?;
+        Some(self.remove_at(index))
+    }
+
+    // All items in the order `pop` would return them.
+    fn into_sorted_vec(mut self) -> Vec<(T, P)> {
+        let mut sorted = Vec::with_capacity(self.len());
+        while let Some(item) = self.pop() {
+            sorted.push(item);
+        }
+        sorted
+    }
+
+    fn index_of(&self, handle: Handle) -> Option<usize> {
+        let slot = self.slots.get(handle.id)?;
+        if slot.gen != handle.gen {
+            return None;
+        }
+        slot.pos
+    }
+
+    // Should `a` be closer to the top than `b`?
+    fn before(&self, a: usize, b: usize) -> bool {
+        match self.order {
+            Order::Max => self.data[a].prio > self.data[b].prio,
+            Order::Min => self.data[a].prio < self.data[b].prio,
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.data.swap(a, b);
+        self.slots[self.data[a].id].pos = Some(a);
+        self.slots[self.data[b].id].pos = Some(b);
+    }
+
+    fn remove_at(&mut self, index: usize) -> (T, P) {
+        // Move the last item into the hole, then restore the heap from there.
+        let last = self.data.len() - 1;
+        self.swap(index, last);
+        let entry = self.data.pop().unwrap();
+        let slot = &mut self.slots[entry.id];
+        slot.gen += 1;
+        slot.pos = None;
+        self.free.push(entry.id);
+        if index < self.data.len() {
+            self.sift_up(index);
+            self.sift_down(self.slots[self.data[index].id].pos.unwrap());
+        }
+        (entry.val, entry.prio)
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if !self.before(index, parent) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let left = 2 * index + 1;
+            let right = left + 1;
+            let mut top = index;
+            if left < self.len() && self.before(left, top) { top = left; }
+            if right < self.len() && self.before(right, top) { top = right; }
+            if top == index {
+                break;
+            }
+            self.swap(index, top);
+            index = top;
+        }
+    }
+
+    // IMPLEMENTATION OF ITERATION FOR THE PRIORITY QUEUE.
+
+    // Queue modified and turned into an iterator, highest priority first.
+    fn into_iter(self) -> IntoIter<T, P> {
+        IntoIter(self)
+    }
+
+    // Returning an immutable iterator, in heap order.
+    fn iter(&self) -> Iter<T, P> {
+        let mut iterator = Iter { queue: Vec::new() };
+        for entry in self.data.iter() {
+            iterator.queue.push((&entry.val, &entry.prio));
+        }
+        iterator
+    }
+
+    // Returning a mutable iterator over the items, in heap order.
+    // Priorities stay read-only, changing them would break the heap.
+    fn iter_mut(&mut self) -> IterMut<T, P> {
+        let mut iterator = IterMut { queue: Vec::new() };
+        for entry in self.data.iter_mut() {
+            iterator.queue.push((&mut entry.val, &entry.prio));
+        }
+        iterator
+    }
+}
+
+// INTOITER
+struct IntoIter<T, P: Ord>(PriorityQueue<T, P>);
+impl<T, P: Ord> Iterator for IntoIter<T, P> {
+    type Item = (T, P);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+// ITER
+struct Iter<'a, T: 'a, P: 'a> { queue: Vec<(&'a T, &'a P)> }
+impl<'a, T, P> Iterator for Iter<'a, T, P> {
+    type Item = (&'a T, &'a P);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.queue.len() != 0 {
+            Some(self.queue.remove(0))
+        } else {
+            None
+        }
+    }
+}
+
+// ITERMUT
+struct IterMut<'a, T: 'a, P: 'a> { queue: Vec<(&'a mut T, &'a P)> }
+impl<'a, T, P> Iterator for IterMut<'a, T, P> {
+    type Item = (&'a mut T, &'a P);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.queue.len() != 0 {
+            Some(self.queue.remove(0))
+        } else {
+            None
+        }
+    }
+}
+
+fn main() {
+    basic_ops();
+    handle_ops();
+    dijkstra_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let mut q = PriorityQueue::new(4);
+        q.push("Huxley", 2).unwrap(); q.push("Adonijah", 9).unwrap();
+        q.push("Harriet", 5).unwrap(); q.push("Merit", 7).unwrap();
+        if let Err(error) = q.push("Herine", 1) {
+            println!("Push error is: {error}");
+        }
+        println!("peek: {:?}, len: {}, full: {}", q.peek(), q.len(), q.is_full());
+        println!("pop: {:?}", q.pop());
+        print!("iter (heap order): ");
+        for (item, prio) in q.iter() {
+            print!("{item}:{prio}, ");
+        }
+        println!("\nsorted: {:?}", q.into_sorted_vec());
+
+        let mut q = PriorityQueue::new_min(4);
+        q.push("Joseph", 3).unwrap(); q.push("Fourier", 1).unwrap(); q.push("Euler", 2).unwrap();
+        println!("min-heap order: {:?}", q.into_iter().collect::<Vec<_>>());
+        println!("=========================");
+    }
+
+    fn handle_ops() {
+        println!("HANDLES");
+        let mut q = PriorityQueue::new(8);
+        let a = q.push("backup", 1).unwrap();
+        let b = q.push("deploy", 5).unwrap();
+        q.push("report", 3).unwrap();
+        println!("top before: {:?}", q.peek());
+        println!("backup raised, old priority {:?}", q.change_priority(a, 10));
+        println!("top after: {:?}", q.peek());
+        println!("decrease deploy to 4: {:?}", q.decrease_key(b, 4));
+        println!("decrease deploy to 9: {:?}", q.decrease_key(b, 9));
+        println!("remove deploy: {:?}", q.remove(b));
+        println!("stale handle: {:?}", q.get(b));
+        println!("=========================");
+    }
+
+    fn dijkstra_ops() {
+        println!("DIJKSTRA WITH DECREASE-KEY");
+        // Edges as (from, to, weight).
+        let edges = [(0, 1, 4), (0, 2, 1), (2, 1, 2), (1, 3, 1), (2, 3, 5), (3, 4, 3)];
+        let n = 5;
+        let mut dist = vec![u32::MAX; n];
+        let mut q = PriorityQueue::new_min(n);
+        let mut handles = Vec::new();
+        for node in 0..n {
+            handles.push(q.push(node, u32::MAX).unwrap());
+        }
+        q.decrease_key(handles[0], 0).unwrap();
+        while let Some((node, d)) = q.pop() {
+            dist[node] = d;
+            if d == u32::MAX {
+                continue;
+            }
+            for &(from, to, w) in edges.iter() {
+                if from == node {
+                    if let Some((_, &old)) = q.get(handles[to]) {
+                        if d + w < old {
+                            q.decrease_key(handles[to], d + w).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        println!("distances from 0: {:?}", dist);
+        println!("=========================");
+    }
+}
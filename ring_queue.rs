@@ -0,0 +1,417 @@
+//! Lock-free ring queues.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// Two bounded FIFO queues on a fixed ring buffer, for low latency paths
+/// where the Mutex around rusqueue.rs's `Queue<T>` costs too much.
+///
+/// `spsc_queue(cap)` is a wait-free single producer, single consumer ring.
+/// The producer only writes `tail`, the consumer only writes `head`.
+///
+/// `MpmcQueue` takes any number of producers and consumers. Every slot has a
+/// sequence number telling whose turn it is (Dmitry Vyukov's scheme), so a
+/// thread claims a slot with a single compare-and-swap.
+///
+/// Both keep the `enqueue`/`dequeue` names and hold at most `cap` items.
+/// A failed `enqueue` hands the value back instead of an error string.
+/// Build with `rustc -O ring_queue.rs` for meaningful benchmark numbers.
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+// Keeps `head` and `tail` on separate cache lines, so producers and
+// consumers do not invalidate each other's line on every update.
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.0 }
+}
+
+// SPSC RING.
+
+struct Spsc<T> {
+    cap: usize,
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // Next position to read, written by the consumer only.
+    head: CachePadded<AtomicUsize>,
+    // Next position to write, written by the producer only.
+    tail: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Sync for Spsc<T> {}
+
+impl<T> Drop for Spsc<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.0.get_mut(), *self.tail.0.get_mut());
+        for pos in head..tail {
+            unsafe { (*self.buffer[pos % self.cap].get()).assume_init_drop(); }
+        }
+    }
+}
+
+/// Writing half of a SPSC ring.
+struct SpscProducer<T> {
+    ring: Arc<Spsc<T>>,
+    // Last head seen, only reloaded when the ring looks full.
+    head: usize,
+}
+
+/// Reading half of a SPSC ring.
+struct SpscConsumer<T> {
+    ring: Arc<Spsc<T>>,
+    // Last tail seen, only reloaded when the ring looks empty.
+    tail: usize,
+}
+
+unsafe impl<T: Send> Send for SpscProducer<T> {}
+unsafe impl<T: Send> Send for SpscConsumer<T> {}
+
+// The two halves can each move to their own thread.
+fn spsc_queue<T>(cap: usize) -> (SpscProducer<T>, SpscConsumer<T>) {
+    let buffer = (0..cap).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect();
+    let ring = Arc::new(Spsc {
+        cap,
+        buffer,
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+    });
+    (SpscProducer { ring: Arc::clone(&ring), head: 0 }, SpscConsumer { ring, tail: 0 })
+}
+
+impl<T> SpscProducer<T> {
+    fn cap(&self) -> usize { self.ring.cap }
+
+    fn len(&self) -> usize {
+        self.ring.tail.load(Ordering::Relaxed) - self.ring.head.load(Ordering::Acquire)
+    }
+
+    // enqueue, or hand the value back when the ring is full.
+    fn enqueue(&mut self, val: T) -> Result<(), T> {
+        let ring = &*self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        if tail - self.head == ring.cap {
+            self.head = ring.head.load(Ordering::Acquire);
+            if tail - self.head == ring.cap {
+                return Err(val);
+            }
+        }
+        unsafe { (*ring.buffer[tail % ring.cap].get()).write(val); }
+        ring.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T> SpscConsumer<T> {
+    fn cap(&self) -> usize { self.ring.cap }
+
+    fn len(&self) -> usize {
+        self.ring.tail.load(Ordering::Acquire) - self.ring.head.load(Ordering::Relaxed)
+    }
+
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    // dequeue, None when the ring is empty.
+    fn dequeue(&mut self) -> Option<T> {
+        let ring = &*self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head == self.tail {
+            self.tail = ring.tail.load(Ordering::Acquire);
+            if head == self.tail {
+                return None;
+            }
+        }
+        let val = unsafe { (*ring.buffer[head % ring.cap].get()).assume_init_read() };
+        ring.head.store(head + 1, Ordering::Release);
+        Some(val)
+    }
+}
+
+// MPMC QUEUE.
+
+struct Slot<T> {
+    // 2 * pos: free for the enqueue at pos. 2 * pos + 1: holds the item for
+    // the dequeue at pos. Dequeuing sets it to 2 * (pos + cap), the next
+    // lap's write. Doubling keeps the two states apart even when cap is 1.
+    seq: AtomicUsize,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+struct MpmcQueue<T> {
+    cap: usize,
+    buffer: Box<[Slot<T>]>,
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Send for MpmcQueue<T> {}
+unsafe impl<T: Send> Sync for MpmcQueue<T> {}
+
+impl<T> MpmcQueue<T> {
+    fn new(cap: usize) -> Self {
+        assert!(cap > 0, "MpmcQueue needs a capacity of at least 1");
+        let buffer = (0..cap).map(|pos| Slot {
+            seq: AtomicUsize::new(2 * pos),
+            val: UnsafeCell::new(MaybeUninit::uninit()),
+        }).collect();
+        Self {
+            cap,
+            buffer,
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+        }
+    }
+
+    fn cap(&self) -> usize { self.cap }
+
+    // Only exact while no other thread is using the queue.
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.saturating_sub(head)
+    }
+
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    // enqueue, or hand the value back when the queue is full.
+    fn enqueue(&self, val: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[pos % self.cap];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as isize - (2 * pos) as isize;
+            if diff == 0 {
+                // The slot is free for this lap, try to claim it.
+                match self.tail.compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        unsafe { (*slot.val.get()).write(val); }
+                        slot.seq.store(2 * pos + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // Still holds last lap's item: the queue is full.
+                return Err(val);
+            } else {
+                // Another producer got here first.
+                pos = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // dequeue, None when the queue is empty.
+    fn dequeue(&self) -> Option<T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[pos % self.cap];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as isize - (2 * pos + 1) as isize;
+            if diff == 0 {
+                match self.head.compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let val = unsafe { (*slot.val.get()).assume_init_read() };
+                        slot.seq.store(2 * (pos + self.cap), Ordering::Release);
+                        return Some(val);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // Not written yet: the queue is empty.
+                return None;
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<T> Drop for MpmcQueue<T> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
+
+// The Vec-backed queue of rusqueue.rs, the baseline for the benchmarks.
+#[derive(Debug)]
+struct Queue<T> {
+    cap: usize, // Capacity
+    data: Vec<T>, // Storing elements
+}
+
+impl<T> Queue<T> {
+    fn new(size: usize) -> Self {
+        Self { cap: size, data: Vec::with_capacity(size) }
+    }
+
+    fn len(&self) -> usize { self.data.len() }
+
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        if self.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<T> {
+        if self.len() > 0 {
+            self.data.pop()
+        } else {
+            None
+        }
+    }
+}
+
+fn main() {
+    basic_ops();
+    check_ops();
+    bench_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let (mut tx, mut rx) = spsc_queue(2);
+        println!("spsc: {:?} {:?} {:?}", tx.enqueue("Joseph"), tx.enqueue("Fourier"), tx.enqueue("Euler"));
+        println!("spsc: {:?} {:?} {:?}", rx.dequeue(), rx.dequeue(), rx.dequeue());
+        let q = MpmcQueue::new(2);
+        println!("mpmc: {:?} {:?} {:?}", q.enqueue("Leonhard"), q.enqueue("Euler"), q.enqueue("Simon"));
+        println!("mpmc: {:?} {:?} {:?}", q.dequeue(), q.dequeue(), q.dequeue());
+        let q = MpmcQueue::new(1);
+        println!("mpmc of 1: {:?} {:?} {:?}", q.enqueue("Carl"), q.enqueue("Gauss"), q.dequeue());
+        println!("=========================");
+    }
+
+    fn check_ops() {
+        println!("CONCURRENT CHECKS");
+        // SPSC keeps the order of the single producer.
+        let (mut tx, mut rx) = spsc_queue(64);
+        let producer = thread::spawn(move || {
+            for i in 0..100_000 {
+                let mut val = i;
+                while let Err(back) = tx.enqueue(val) {
+                    val = back;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 100_000 {
+            match rx.dequeue() {
+                Some(val) => {
+                    assert_eq!(val, expected, "spsc out of order");
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        println!("spsc: 100000 items in order");
+
+        // MPMC hands every item to exactly one consumer, down to a single slot.
+        for &cap in [1, 64].iter() {
+            let q = MpmcQueue::new(cap);
+            let taken = AtomicUsize::new(0);
+            let sums: Vec<u64> = thread::scope(|scope| {
+                for p in 0..4u64 {
+                    let q = &q;
+                    scope.spawn(move || {
+                        for i in 0..25_000 {
+                            let mut val = p * 25_000 + i;
+                            while let Err(back) = q.enqueue(val) {
+                                val = back;
+                                thread::yield_now();
+                            }
+                        }
+                    });
+                }
+                let consumers: Vec<_> = (0..4).map(|_| {
+                    let (q, taken) = (&q, &taken);
+                    scope.spawn(move || {
+                        let mut sum = 0;
+                        while taken.load(Ordering::Relaxed) < 100_000 {
+                            match q.dequeue() {
+                                Some(val) => {
+                                    sum += val;
+                                    taken.fetch_add(1, Ordering::Relaxed);
+                                }
+                                None => thread::yield_now(),
+                            }
+                        }
+                        sum
+                    })
+                }).collect();
+                consumers.into_iter().map(|c| c.join().unwrap()).collect()
+            });
+            assert_eq!(sums.iter().sum::<u64>(), (0..100_000).sum::<u64>());
+            println!("mpmc of {}: 100000 items, each taken once", cap);
+        }
+        println!("=========================");
+    }
+
+    fn bench_ops() {
+        println!("THROUGHPUT (items per second)");
+        let items = 1_000_000;
+        let cap = 1024;
+
+        // One producer, one consumer.
+        let start = Instant::now();
+        let (mut tx, mut rx) = spsc_queue(cap);
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..items {
+                    let mut val = i;
+                    while let Err(back) = tx.enqueue(val) { val = back; thread::yield_now(); }
+                }
+            });
+            let mut got = 0;
+            while got < items {
+                if rx.dequeue().is_some() { got += 1; } else { thread::yield_now(); }
+            }
+        });
+        report("spsc ring, 1p/1c", items, start);
+
+        for &threads in [1, 4].iter() {
+            let start = Instant::now();
+            let q = MpmcQueue::new(cap);
+            run_mpmc(threads, items, |val| q.enqueue(val).is_ok(), || q.dequeue().is_some());
+            report(&format!("mpmc queue, {0}p/{0}c", threads), items, start);
+
+            let start = Instant::now();
+            let q = Mutex::new(Queue::new(cap));
+            run_mpmc(threads, items,
+                     |val| q.lock().unwrap().enqueue(val).is_ok(),
+                     || q.lock().unwrap().dequeue().is_some());
+            report(&format!("Mutex<Queue>, {0}p/{0}c", threads), items, start);
+        }
+        println!("=========================");
+
+        fn run_mpmc<E, D>(threads: usize, items: usize, enqueue: E, dequeue: D)
+        where E: Fn(usize) -> bool + Sync, D: Fn() -> bool + Sync {
+            let per_thread = items / threads;
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let (enqueue, dequeue) = (&enqueue, &dequeue);
+                    scope.spawn(move || {
+                        for i in 0..per_thread {
+                            while !enqueue(i) { thread::yield_now(); }
+                        }
+                    });
+                    scope.spawn(move || {
+                        let mut got = 0;
+                        while got < per_thread {
+                            if dequeue() { got += 1; } else { thread::yield_now(); }
+                        }
+                    });
+                }
+            });
+        }
+
+        fn report(name: &str, items: usize, start: Instant) {
+            let secs = start.elapsed().as_secs_f64();
+            println!("{:24} {:>12.0}", name, items as f64 / secs);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Queue data structure.
 #![allow(warnings)] // Supress all warnings for the entire crate.
+use std::ops::{Deref, DerefMut};
 
 #[derive(Debug)]
 struct Queue<T> {
@@ -38,6 +39,62 @@ impl<T> Queue<T> {
         }
     }
 
+    // The value that would be dequeued next.
+    fn peek(&self) -> Option<&T> {
+        self.data.last()
+    }
+
+    // A guard on the next value: edit it in place, or dequeue it with
+    // `PeekMut::pop` after looking at it.
+    fn peek_mut(&mut self) -> Option<PeekMut<T>> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(PeekMut { queue: self })
+    }
+
+    // BULK OPERATIONS.
+    //
+    // Adding fails as a whole, leaving the queue unchanged, when the items
+    // would not fit in `cap`.
+
+    fn check_space(&self, n: usize) -> Result<(), String> {
+        let free = self.cap - self.len();
+        if n > free {
+            return Err(format!("No space available for {n} items, {free} free"));
+        }
+        Ok(())
+    }
+
+    // Enqueue every item of `other`, oldest first, emptying it.
+    fn append(&mut self, other: &mut Queue<T>) -> Result<(), String> {
+        self.check_space(other.len())?;
+        other.data.append(&mut self.data);
+        std::mem::swap(&mut self.data, &mut other.data);
+        Ok(())
+    }
+
+    // Enqueue every item in turn.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) -> Result<(), String> {
+        let items: Vec<T> = items.into_iter().collect();
+        self.check_space(items.len())?;
+        self.data.splice(0..0, items.into_iter().rev());
+        Ok(())
+    }
+
+    // Keep only the items for which `pred` is true, in the same order.
+    fn retain<F: FnMut(&T) -> bool>(&mut self, pred: F) {
+        self.data.retain(pred);
+    }
+
+    // Keep the `n` items that would be dequeued first, dropping the newest.
+    fn truncate(&mut self, n: usize) {
+        if n < self.len() {
+            let newest = self.len() - n;
+            self.data.drain(..newest);
+        }
+    }
+
     // IMPLEMENTATION OF ITERATION FOR THE QUEUE.
 
     // Queue modified and turned into an iterator
@@ -64,6 +121,29 @@ impl<T> Queue<T> {
     }
 }
 
+// PEEKMUT: the next value of a non-empty queue.
+struct PeekMut<'a, T: 'a> { queue: &'a mut Queue<T> }
+
+impl<'a, T> PeekMut<'a, T> {
+    // Dequeue the peeked value.
+    fn pop(this: PeekMut<'a, T>) -> T {
+        this.queue.dequeue().unwrap()
+    }
+}
+
+impl<'a, T> Deref for PeekMut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.queue.data.last().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PeekMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.queue.data.last_mut().unwrap()
+    }
+}
+
 // INTOITER 
 struct IntoIter<T>(Queue<T>);
 impl<T> Iterator for IntoIter<T> {
@@ -104,9 +184,42 @@ impl<'a, T> Iterator for IterMut<'a, T> {
 }
 
 
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Fifo<T>: Container {
+    fn enqueue(&mut self, val: T) -> Result<(), String>;
+    fn dequeue(&mut self) -> Option<T>;
+}
+
+impl<T> Container for Queue<T> {
+    fn len(&self) -> usize { Queue::len(self) }
+    fn clear(&mut self) { Queue::clear(self) }
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+}
+
+impl<T> Fifo<T> for Queue<T> {
+    fn enqueue(&mut self, val: T) -> Result<(), String> { Queue::enqueue(self, val) }
+    fn dequeue(&mut self) -> Option<T> { Queue::dequeue(self) }
+}
+
+// Take up to `n` items off any queue.
+fn take<T, Q: Fifo<T>>(queue: &mut Q, n: usize) -> Vec<T> {
+    (0..n).filter_map(|_| queue.dequeue()).collect()
+}
+
 fn main() {
     basic_queue_ops();
     queue_iter_ops();
+    bulk_queue_ops();
+    peek_queue_ops();
+    trait_queue_ops();
 
     // Testing the queue operations.
     fn basic_queue_ops() {
@@ -149,4 +262,65 @@ fn main() {
         }
         println!("\n============test done===============");
     }
+
+    // Testing the bulk operations.
+    fn bulk_queue_ops() {
+        println!("BULK OPERATIONS");
+        let mut q = Queue::new(6);
+        q.extend(vec![1, 2, 3]).unwrap();
+        let mut other = Queue::new(3);
+        other.extend(vec![4, 5]).unwrap();
+        q.append(&mut other).unwrap();
+        println!("appended: {:?}, other empty: {}", q, other.is_empty());
+        if let Err(error) = q.extend(vec![6, 7]) {
+            println!("Extend error is: {error}");
+        }
+        q.retain(|&x| x != 2);
+        q.truncate(3);
+        print!("retain and truncate(3), dequeue order: ");
+        while let Some(x) = q.dequeue() {
+            print!("{x}, ");
+        }
+        println!("\n=========================");
+    }
+
+    // Testing peek and the PeekMut guard with a small dispatcher: the head
+    // job is only dequeued once a worker can take it, otherwise it waits
+    // with one more attempt counted.
+    fn peek_queue_ops() {
+        println!("PEEK OPERATIONS");
+        #[derive(Debug)]
+        struct Job { name: &'static str, needs: u32, attempts: u32 }
+        let mut q = Queue::new(4);
+        q.enqueue(Job { name: "resize", needs: 2, attempts: 0 }).unwrap();
+        q.enqueue(Job { name: "encode", needs: 1, attempts: 0 }).unwrap();
+        println!("head: {:?}", q.peek().map(|job| job.name));
+        for free_workers in [1, 3, 1] {
+            if let Some(mut job) = q.peek_mut() {
+                if job.needs <= free_workers {
+                    let job = PeekMut::pop(job);
+                    println!("{free_workers} free: dispatched {} after {} waits", job.name, job.attempts);
+                } else {
+                    job.attempts += 1;
+                    println!("{free_workers} free: {} waits", job.name);
+                }
+            }
+        }
+        println!("empty: {}, peek_mut: {}", q.is_empty(), q.peek_mut().is_none());
+        println!("=========================");
+    }
+
+    // Testing the queue through the Fifo trait.
+    fn trait_queue_ops() {
+        println!("FIFO TRAIT");
+        let mut q = Queue::new(4);
+        for job in ["build", "test", "deploy"] {
+            Fifo::enqueue(&mut q, job).unwrap();
+        }
+        println!("take 2: {:?}", take(&mut q, 2));
+        println!("len: {}, capacity: {:?}", Container::len(&q), Container::capacity(&q));
+        Container::clear(&mut q);
+        println!("after clear: {:?}", q);
+        println!("=========================");
+    }
 }
@@ -125,10 +125,54 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     }
 }
 
+// CONTAINER TRAITS, documented in linked_containers.rs.
+
+trait Container {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+    fn capacity(&self) -> Option<usize>;
+}
+
+trait Lifo<T>: Container {
+    fn push(&mut self, val: T) -> Result<(), String>;
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<&T>;
+}
+
+impl<T> Container for Stack<T> {
+    fn len(&self) -> usize { Stack::len(self) }
+    fn clear(&mut self) { Stack::clear(self) }
+    fn capacity(&self) -> Option<usize> { None }
+}
+
+impl<T> Lifo<T> for Stack<T> {
+    fn push(&mut self, val: T) -> Result<(), String> {
+        Stack::push(self, val);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> { Stack::pop(self) }
+    fn peek(&self) -> Option<&T> { Stack::peek(self) }
+}
+
+// Reverse a string on any stack.
+fn reverse<S: Lifo<char>>(stack: &mut S, text: &str) -> Result<String, String> {
+    for c in text.chars() {
+        stack.push(c)?;
+    }
+    let mut reversed = String::new();
+    while let Some(c) = stack.pop() {
+        reversed.push(c);
+    }
+    Ok(reversed)
+}
+
 fn main() {
     primary();
     peek_ops();
     iter_ops();
+    trait_ops();
 
     fn primary() {
         let mut s = Stack::new();
@@ -169,4 +213,11 @@ fn main() {
         }
         println!("\n");
     }
+
+    fn trait_ops() {
+        let mut s = Stack::new();
+        println!("Reversed through the Lifo trait: {:?}", reverse(&mut s, "stressed"));
+        println!("Capacity: {:?}, empty after: {}", Container::capacity(&s), Container::is_empty(&s));
+        println!("\n");
+    }
 }
@@ -0,0 +1,754 @@
+//! Container snapshots.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// Saves the contents of `Stack<T>`, `Queue<T>` and `Deque<T>` (copies of
+/// rustack.rs, rusqueue.rs and deckrus.rs) to a compact binary form or to
+/// JSON, and reads them back with the same items, order and capacity.
+/// No dependencies.
+///
+/// An optional `serde` feature was asked for but is not provided: these
+/// files are built one at a time with plain rustc, with no Cargo manifest
+/// to declare the feature or the serde dependency, so such code could never
+/// be compiled or run here. The `Codec` and `Snapshot` traits play that
+/// role instead.
+///
+/// Items are always written in their logical order: a stack bottom to top,
+/// a queue front to rear and a deque front to back, whatever the internal
+/// layout.
+///
+/// Binary layout, integers little endian:
+///     magic "CSNP" | version: u16 | kind: u8 | has cap: u8 | cap: u64
+///     | item count: u64 | items
+/// JSON:
+///     {"format":"CSNP","version":1,"kind":"queue","cap":4,"items":[...]}
+/// Readers match on the version first: a new format gets its own arm in
+/// `from_binary`/`from_json`, and the old arms keep reading old snapshots.
+use std::fmt;
+
+#[derive(Debug)]
+struct Stack<T> {
+    size: usize,
+    data: Vec<T>,
+}
+
+impl<T> Stack<T> {
+    fn new() -> Self {
+        Self { size: 0, data: Vec::new() }
+    }
+
+    fn len(&self) -> usize { self.size }
+
+    fn push(&mut self, val: T) {
+        self.data.push(val);
+        self.size += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.size == 0 {
+            return None
+        }
+        self.size -= 1;
+        self.data.pop()
+    }
+}
+
+#[derive(Debug)]
+struct Queue<T> {
+    cap: usize, // Capacity
+    data: Vec<T>, // Storing elements
+}
+
+impl<T> Queue<T> {
+    fn new(size: usize) -> Self {
+        Self { cap: size, data: Vec::with_capacity(size) }
+    }
+
+    fn len(&self) -> usize { self.data.len() }
+
+    // enqueue or add data to the queue
+    fn enqueue(&mut self, val: T) -> Result<(), String> {
+        if self.len() == self.cap {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    // pop out values.
+    fn dequeue(&mut self) -> Option<T> {
+        self.data.pop()
+    }
+}
+
+#[derive(Debug)]
+struct Deque<T> {
+    cap: usize,
+    data: Vec<T>,
+}
+
+impl<T> Deque<T> {
+    fn new(cap: usize) -> Self {
+        Self { cap: cap, data: Vec::with_capacity(cap) }
+    }
+
+    fn len(&self) -> usize { self.data.len() }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+
+    // Adding data at the front or start of the deque
+    fn push_front(&mut self, val: T) -> Result<(), String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    // Adding data at the back or end of the deque
+    fn push_back(&mut self, val: T) -> Result<(), String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        self.data.push(val);
+        Ok(())
+    }
+
+    // Remove data from the start or front of the deque
+    fn pop_front(&mut self) -> Option<T> {
+        if self.len() > 0 {
+            Some(self.data.remove(0))
+        } else {
+            None
+        }
+    }
+}
+
+const MAGIC: &[u8; 4] = b"CSNP";
+const VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    WrongKind { expected: Kind, found: String },
+    Truncated,
+    OverCapacity { cap: usize, items: usize },
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a container snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "snapshot version {v} is newer than {VERSION}"),
+            SnapshotError::WrongKind { expected, found } => {
+                write!(f, "expected a {} snapshot, found {}", expected.name(), found)
+            }
+            SnapshotError::Truncated => write!(f, "snapshot ends early"),
+            SnapshotError::OverCapacity { cap, items } => {
+                write!(f, "{items} items do not fit in capacity {cap}")
+            }
+            SnapshotError::Invalid(msg) => write!(f, "invalid snapshot: {msg}"),
+        }
+    }
+}
+
+fn invalid<T>(msg: &str) -> Result<T, SnapshotError> {
+    Err(SnapshotError::Invalid(msg.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Stack,
+    Queue,
+    Deque,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Stack => "stack",
+            Kind::Queue => "queue",
+            Kind::Deque => "deque",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Kind::Stack => 0,
+            Kind::Queue => 1,
+            Kind::Deque => 2,
+        }
+    }
+}
+
+// BINARY INPUT.
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() - self.pos < n {
+            return Err(SnapshotError::Truncated);
+        }
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+// JSON INPUT.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String), // Kept as text so large integers stay exact
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|f| f.0 == key).map(|f| &f.1),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    // The same input as text, already checked as UTF-8, for strings.
+    src: &'a str,
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(text: &'a str) -> Result<Json, SnapshotError> {
+        let mut parser = Parser { src: text, text: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos != parser.text.len() {
+            return invalid("trailing characters after JSON");
+        }
+        Ok(value)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.pos < self.text.len() && (self.text[self.pos] as char).is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8, SnapshotError> {
+        self.skip_ws();
+        self.text.get(self.pos).cloned().ok_or(SnapshotError::Truncated)
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SnapshotError> {
+        if self.peek()? != c {
+            return invalid(&format!("expected '{}' at byte {}", c as char, self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, SnapshotError> {
+        if !self.text[self.pos..].starts_with(word.as_bytes()) {
+            return invalid(&format!("unexpected token at byte {}", self.pos));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, SnapshotError> {
+        match self.peek()? {
+            b'n' => self.literal("null", Json::Null),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'"' => Ok(Json::Str(self.string()?)),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek()? == b']' {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek()? {
+                        b',' => self.pos += 1,
+                        b']' => { self.pos += 1; return Ok(Json::Array(items)); }
+                        _ => return invalid(&format!("expected ',' or ']' at byte {}", self.pos)),
+                    }
+                }
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.peek()? == b'}' {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    if self.peek()? != b'"' {
+                        return invalid(&format!("expected a key at byte {}", self.pos));
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    match self.peek()? {
+                        b',' => self.pos += 1,
+                        b'}' => { self.pos += 1; return Ok(Json::Object(fields)); }
+                        _ => return invalid(&format!("expected ',' or '}}' at byte {}", self.pos)),
+                    }
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.pos;
+                while self.pos < self.text.len() && b"+-.eE0123456789".contains(&self.text[self.pos]) {
+                    self.pos += 1;
+                }
+                Ok(Json::Number(String::from_utf8_lossy(&self.text[start..self.pos]).into_owned()))
+            }
+            _ => invalid(&format!("unexpected character at byte {}", self.pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        self.pos += 1; // Opening quote
+        let mut out = String::new();
+        loop {
+            // Only the next character is decoded, `pos` is always on a
+            // character boundary here.
+            let c = match self.src.get(self.pos..).and_then(|rest| rest.chars().next()) {
+                Some(c) => c,
+                None => return Err(SnapshotError::Truncated),
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let esc = *self.text.get(self.pos).ok_or(SnapshotError::Truncated)?;
+                    self.pos += 1;
+                    match esc {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // A surrogate pair spells one character.
+                            if (0xD800..0xDC00).contains(&code) && self.text[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            out.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => return invalid(&format!("bad escape at byte {}", self.pos - 1)),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, SnapshotError> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or(SnapshotError::Truncated)?;
+        self.pos += 4;
+        match u32::from_str_radix(&String::from_utf8_lossy(digits), 16) {
+            Ok(code) => Ok(code),
+            Err(_) => invalid("bad \\u escape"),
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// ITEM ENCODING.
+
+/// How a single item is written and read in both formats.
+trait Codec: Sized {
+    fn write_binary(&self, out: &mut Vec<u8>);
+    fn read_binary(r: &mut Reader) -> Result<Self, SnapshotError>;
+    fn write_json(&self, out: &mut String);
+    fn read_json(value: &Json) -> Result<Self, SnapshotError>;
+}
+
+macro_rules! int_codec {
+    ($($t:ty),*) => {$(
+        impl Codec for $t {
+            fn write_binary(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_binary(r: &mut Reader) -> Result<Self, SnapshotError> {
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                buf.copy_from_slice(r.take(std::mem::size_of::<$t>())?);
+                Ok(<$t>::from_le_bytes(buf))
+            }
+
+            fn write_json(&self, out: &mut String) {
+                out.push_str(&self.to_string());
+            }
+
+            fn read_json(value: &Json) -> Result<Self, SnapshotError> {
+                match value {
+                    Json::Number(text) => match text.parse() {
+                        Ok(n) => Ok(n),
+                        Err(_) => invalid(&format!("{text} is not a {}", stringify!($t))),
+                    },
+                    _ => invalid(concat!("expected a number for ", stringify!($t))),
+                }
+            }
+        }
+    )*};
+}
+
+int_codec!(i32, i64, u32, u64);
+
+impl Codec for bool {
+    fn write_binary(&self, out: &mut Vec<u8>) { out.push(*self as u8); }
+
+    fn read_binary(r: &mut Reader) -> Result<Self, SnapshotError> {
+        match r.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => invalid("bad bool"),
+        }
+    }
+
+    fn write_json(&self, out: &mut String) { out.push_str(if *self { "true" } else { "false" }); }
+
+    fn read_json(value: &Json) -> Result<Self, SnapshotError> {
+        match value {
+            Json::Bool(b) => Ok(*b),
+            _ => invalid("expected a bool"),
+        }
+    }
+}
+
+impl Codec for String {
+    fn write_binary(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).write_binary(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn read_binary(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = r.u64()? as usize;
+        match String::from_utf8(r.take(len)?.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => invalid("string is not UTF-8"),
+        }
+    }
+
+    fn write_json(&self, out: &mut String) { write_json_string(out, self); }
+
+    fn read_json(value: &Json) -> Result<Self, SnapshotError> {
+        match value {
+            Json::Str(s) => Ok(s.clone()),
+            _ => invalid("expected a string"),
+        }
+    }
+}
+
+// CONTAINER SNAPSHOTS.
+
+/// A container that can be saved and restored. Implementors list their
+/// items in logical order and rebuild themselves from such a list, the
+/// formats are handled here.
+trait Snapshot: Sized {
+    type Item: Codec;
+    const KIND: Kind;
+
+    fn capacity(&self) -> Option<usize>;
+    fn items(&self) -> Vec<&Self::Item>;
+    fn rebuild(cap: Option<usize>, items: Vec<Self::Item>) -> Result<Self, SnapshotError>;
+
+    fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        VERSION.to_le_bytes().iter().for_each(|&b| out.push(b));
+        out.push(Self::KIND.code());
+        out.push(self.capacity().is_some() as u8);
+        (self.capacity().unwrap_or(0) as u64).write_binary(&mut out);
+        let items = self.items();
+        (items.len() as u64).write_binary(&mut out);
+        for item in items {
+            item.write_binary(&mut out);
+        }
+        out
+    }
+
+    fn from_binary(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(4).map_err(|_| SnapshotError::BadMagic)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let (cap, count) = match r.u16()? {
+            1 => {
+                let kind = r.u8()?;
+                if kind != Self::KIND.code() {
+                    return Err(SnapshotError::WrongKind { expected: Self::KIND, found: format!("kind {kind}") });
+                }
+                let has_cap = r.u8()? == 1;
+                let cap = r.u64()? as usize;
+                (if has_cap { Some(cap) } else { None }, r.u64()?)
+            }
+            version => return Err(SnapshotError::UnsupportedVersion(version)),
+        };
+        // Don't trust the count for the allocation, a corrupt one would be huge.
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(Self::Item::read_binary(&mut r)?);
+        }
+        if r.pos != bytes.len() {
+            return invalid("trailing bytes after the items");
+        }
+        Self::rebuild(cap, items)
+    }
+
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("{{\"format\":\"CSNP\",\"version\":{VERSION},\"kind\":\"{}\",\"cap\":", Self::KIND.name()));
+        match self.capacity() {
+            Some(cap) => out.push_str(&cap.to_string()),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"items\":[");
+        for (i, item) in self.items().into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            item.write_json(&mut out);
+        }
+        out.push_str("]}");
+        out
+    }
+
+    fn from_json(text: &str) -> Result<Self, SnapshotError> {
+        let doc = Parser::parse(text)?;
+        if doc.get("format") != Some(&Json::Str("CSNP".to_string())) {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = match doc.get("version") {
+            Some(Json::Number(v)) => v.parse::<u16>().or_else(|_| invalid("bad version"))?,
+            _ => return invalid("missing version"),
+        };
+        let (cap, items) = match version {
+            1 => {
+                match doc.get("kind") {
+                    Some(Json::Str(kind)) if kind == Self::KIND.name() => {}
+                    other => {
+                        let found = format!("{:?}", other.cloned().unwrap_or(Json::Null));
+                        return Err(SnapshotError::WrongKind { expected: Self::KIND, found });
+                    }
+                }
+                let cap = match doc.get("cap") {
+                    Some(Json::Null) => None,
+                    Some(value) => Some(u64::read_json(value)? as usize),
+                    None => return invalid("missing cap"),
+                };
+                let items = match doc.get("items") {
+                    Some(Json::Array(items)) => items,
+                    _ => return invalid("missing items"),
+                };
+                (cap, items)
+            }
+            version => return Err(SnapshotError::UnsupportedVersion(version)),
+        };
+        let items = items.iter().map(Self::Item::read_json).collect::<Result<Vec<_>, _>>()?;
+        Self::rebuild(cap, items)
+    }
+}
+
+fn check_cap(cap: Option<usize>, items: usize) -> Result<usize, SnapshotError> {
+    match cap {
+        Some(cap) if items <= cap => Ok(cap),
+        Some(cap) => Err(SnapshotError::OverCapacity { cap, items }),
+        None => invalid("missing capacity"),
+    }
+}
+
+impl<T: Codec> Snapshot for Stack<T> {
+    type Item = T;
+    const KIND: Kind = Kind::Stack;
+
+    // The stack grows without bound.
+    fn capacity(&self) -> Option<usize> { None }
+
+    // Bottom to top.
+    fn items(&self) -> Vec<&T> { self.data.iter().collect() }
+
+    fn rebuild(cap: Option<usize>, items: Vec<T>) -> Result<Self, SnapshotError> {
+        if cap.is_some() {
+            return invalid("a stack has no capacity");
+        }
+        let mut stack = Stack::new();
+        for item in items {
+            stack.push(item);
+        }
+        Ok(stack)
+    }
+}
+
+impl<T: Codec> Snapshot for Queue<T> {
+    type Item = T;
+    const KIND: Kind = Kind::Queue;
+
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+
+    // Front (next to dequeue) to rear.
+    fn items(&self) -> Vec<&T> { self.data.iter().rev().collect() }
+
+    fn rebuild(cap: Option<usize>, items: Vec<T>) -> Result<Self, SnapshotError> {
+        let mut queue = Queue::new(check_cap(cap, items.len())?);
+        for item in items {
+            let _ = queue.enqueue(item);
+        }
+        Ok(queue)
+    }
+}
+
+impl<T: Codec> Snapshot for Deque<T> {
+    type Item = T;
+    const KIND: Kind = Kind::Deque;
+
+    fn capacity(&self) -> Option<usize> { Some(self.cap) }
+
+    // Front to back, the order `data` already has.
+    fn items(&self) -> Vec<&T> { self.data.iter().collect() }
+
+    fn rebuild(cap: Option<usize>, items: Vec<T>) -> Result<Self, SnapshotError> {
+        let mut deque = Deque::new(check_cap(cap, items.len())?);
+        for item in items {
+            let _ = deque.push_back(item);
+        }
+        Ok(deque)
+    }
+}
+
+fn main() {
+    binary_ops();
+    json_ops();
+    file_ops();
+    error_ops();
+
+    fn binary_ops() {
+        println!("BINARY SNAPSHOTS");
+        let mut q = Queue::new(4);
+        q.enqueue(10u64).unwrap(); q.enqueue(20).unwrap(); q.enqueue(30).unwrap();
+        let bytes = q.to_binary();
+        println!("queue of 3 u64 in {} bytes: {:?}", bytes.len(), &bytes[..24]);
+        let mut copy = Queue::<u64>::from_binary(&bytes).unwrap();
+        let front = copy.dequeue();
+        println!("restored cap {}, dequeue {:?}", copy.cap, front);
+
+        let mut s = Stack::new();
+        s.push(-1i32); s.push(2); s.push(-3);
+        let mut copy = Stack::<i32>::from_binary(&s.to_binary()).unwrap();
+        println!("restored stack pops {:?}, len {}", copy.pop(), copy.len());
+        println!("=========================");
+    }
+
+    fn json_ops() {
+        println!("JSON SNAPSHOTS");
+        let mut deck = Deque::new(5);
+        deck.push_front("Galileo".to_string()).unwrap(); deck.push_back("Galilei".to_string()).unwrap();
+        deck.push_front("say \"hi\"\n".to_string()).unwrap();
+        let json = deck.to_json();
+        println!("{json}");
+        let mut copy = Deque::<String>::from_json(&json).unwrap();
+        assert_eq!(copy.data, deck.data);
+        println!("restored front: {:?}", copy.pop_front());
+        let pretty = "{ \"format\": \"CSNP\", \"version\": 1, \"kind\": \"queue\",\n  \"cap\": 3, \"items\": [true, false] }";
+        let q = Queue::<bool>::from_json(pretty).unwrap();
+        println!("hand-written JSON: {:?}", q);
+        // Parsing is linear in the input: a large snapshot loads at once.
+        let mut big = Queue::new(20_000);
+        for i in 0..20_000 {
+            big.enqueue(format!("job {i}: Größe ✓")).unwrap();
+        }
+        let json = big.to_json();
+        let start = std::time::Instant::now();
+        let copy = Queue::<String>::from_json(&json).unwrap();
+        assert_eq!(copy.data, big.data);
+        assert!(start.elapsed() < std::time::Duration::from_secs(1), "slow parse: {:?}", start.elapsed());
+        println!("{} KB snapshot of {} items restored", json.len() / 1024, copy.data.len());
+        println!("=========================");
+    }
+
+    fn file_ops() {
+        println!("ACROSS PROCESSES");
+        let path = std::env::temp_dir().join(format!("snapshot_{}.bin", std::process::id()));
+        let mut q = Queue::new(3);
+        q.enqueue("Huxley".to_string()).unwrap(); q.enqueue("Merit".to_string()).unwrap();
+        std::fs::write(&path, q.to_binary()).unwrap();
+        let copy = Queue::<String>::from_binary(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(copy.data, q.data);
+        assert_eq!(copy.cap, q.cap);
+        println!("read back from {}: {:?}", path.display(), copy);
+        println!("=========================");
+    }
+
+    fn error_ops() {
+        println!("ERRORS");
+        let mut q = Queue::new(2);
+        q.enqueue(1u32).unwrap(); q.enqueue(2).unwrap();
+        let bytes = q.to_binary();
+        let mut newer = bytes.clone();
+        newer[4] = 9;
+        let errors = vec![
+            Deque::<u32>::from_binary(&bytes).unwrap_err(),
+            Queue::<u32>::from_binary(&newer).unwrap_err(),
+            Queue::<u32>::from_binary(&bytes[..bytes.len() - 1]).unwrap_err(),
+            Queue::<u32>::from_binary(b"PNG?").unwrap_err(),
+            Queue::<u32>::from_json("{\"format\":\"CSNP\",\"version\":1,\"kind\":\"queue\",\"cap\":1,\"items\":[1,2]}").unwrap_err(),
+            Queue::<u32>::from_json("{\"format\":\"CSNP\",\"version\":1,\"kind\":\"queue\",\"cap\":3,\"items\":[1,-2]}").unwrap_err(),
+            Queue::<u32>::from_json("{\"format\":\"CSNP\",\"version\":1,").unwrap_err(),
+            Stack::<u32>::from_json("{\"format\":\"CSNP\",\"version\":1,\"kind\":\"stack\",\"cap\":2,\"items\":[1]}").unwrap_err(),
+        ];
+        for error in errors {
+            println!("{error}");
+        }
+        println!("=========================");
+    }
+}
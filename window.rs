@@ -0,0 +1,322 @@
+//! Sliding windows.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// Queries over the last k items of a sequence, built on the `Deque<T>` of
+/// deckrus.rs: rolling maximum and minimum, moving sum and average, and the
+/// first item matching a condition (such as the first negative) in every
+/// window.
+///
+/// Each query comes as a streaming struct, fed one sample at a time, and
+/// as a function over a slice giving one result per full window.
+///
+/// The maximum keeps a monotonic deque of (index, value) whose values
+/// decrease from the front to the back, so the front holds the maximum. A
+/// new sample goes in at the back after removing the smaller values there,
+/// which can never be the maximum again, and the front is removed once it
+/// falls out of the window. Every sample enters and leaves the deque once:
+/// O(n) deque operations for n samples.
+
+#[derive(Debug)]
+struct Deque<T> {
+    cap: usize,
+    data: Vec<T>,
+}
+
+impl<T> Deque<T> {
+    fn new(cap: usize) -> Self {
+        Self {
+            cap: cap,
+            data: Vec::with_capacity(cap),
+        }
+    }
+
+    fn len(&self) -> usize { self.data.len() }
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn is_full(&self) -> bool { self.len() == self.cap }
+
+    // Adding data at the front or start of the deque
+    fn push_front(&mut self, val: T) -> Result<(), String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        self.data.insert(0, val);
+        Ok(())
+    }
+
+    // Adding data at the back or end of the deque
+    fn push_back(&mut self, val: T) -> Result<(), String> {
+        if self.is_full() {
+            return Err("No space available".to_string());
+        }
+        self.data.push(val);
+        Ok(())
+    }
+
+    // Remove data from the start or front of the deque
+    fn pop_front(&mut self) -> Option<T> {
+        if self.len() > 0 {
+            Some(self.data.remove(0))
+        } else {
+            None
+        }
+    }
+
+    // Remove data from the back or end of the deque
+    fn pop_back(&mut self) -> Option<T> {
+        self.data.pop()
+    }
+
+    fn front(&self) -> Option<&T> { self.data.first() }
+    fn back(&self) -> Option<&T> { self.data.last() }
+
+    // The item at position `i`, counted from the front.
+    fn get(&self, i: usize) -> Option<&T> {
+        self.data.get(i)
+    }
+}
+
+// MAXIMUM AND MINIMUM.
+
+/// Rolling extreme of the last `k` samples. `wins(a, b)` says whether `a`
+/// beats `b`, which makes it a maximum or a minimum.
+struct SlidingExtreme<T> {
+    k: usize,
+    seen: usize,
+    deque: Deque<(usize, T)>,
+    wins: fn(&T, &T) -> bool,
+}
+
+impl<T: Clone> SlidingExtreme<T> {
+    fn with(k: usize, wins: fn(&T, &T) -> bool) -> Self {
+        assert!(k > 0, "window length must be positive");
+        Self { k, seen: 0, deque: Deque::new(k), wins }
+    }
+
+    // Add a sample, returning the extreme of the last `k` samples (fewer
+    // at the start).
+    fn push(&mut self, val: T) -> T {
+        while let Some(&(_, ref last)) = self.deque.back() {
+            if (self.wins)(&val, last) || !(self.wins)(last, &val) {
+                self.deque.pop_back();
+            } else {
+                break;
+            }
+        }
+        if let Some(&(index, _)) = self.deque.front() {
+            if index + self.k <= self.seen {
+                self.deque.pop_front();
+            }
+        }
+        // At most `k` entries, and one just left if the window was full.
+        self.deque.push_back((self.seen, val)).expect("window has room");
+        self.seen += 1;
+        self.current().unwrap()
+    }
+
+    // The extreme of the current window.
+    fn current(&self) -> Option<T> {
+        self.deque.front().map(|entry| entry.1.clone())
+    }
+}
+
+impl<T: Ord + Clone> SlidingExtreme<T> {
+    fn max(k: usize) -> Self {
+        Self::with(k, |a, b| a > b)
+    }
+
+    fn min(k: usize) -> Self {
+        Self::with(k, |a, b| a < b)
+    }
+}
+
+// One result per full window of `k` items.
+fn full_windows<T, R, F: FnMut(T) -> R>(items: Vec<T>, k: usize, mut step: F) -> Vec<R> {
+    let mut out = Vec::new();
+    for (i, item) in items.into_iter().enumerate() {
+        let result = step(item);
+        if i + 1 >= k {
+            out.push(result);
+        }
+    }
+    out
+}
+
+fn sliding_max<T: Ord + Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut window = SlidingExtreme::max(k);
+    full_windows(items.to_vec(), k, |x| window.push(x))
+}
+
+fn sliding_min<T: Ord + Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut window = SlidingExtreme::min(k);
+    full_windows(items.to_vec(), k, |x| window.push(x))
+}
+
+// SUM AND AVERAGE.
+
+/// Running sum of the last `k` samples: add the new one, subtract the one
+/// that leaves.
+struct MovingSum<T> {
+    sum: T,
+    deque: Deque<T>,
+}
+
+impl<T> MovingSum<T>
+where
+    T: Copy + Default + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+{
+    fn new(k: usize) -> Self {
+        assert!(k > 0, "window length must be positive");
+        Self { sum: T::default(), deque: Deque::new(k) }
+    }
+
+    // Add a sample, returning the sum of the last `k` samples.
+    fn push(&mut self, val: T) -> T {
+        if self.deque.is_full() {
+            let old = self.deque.pop_front().unwrap();
+            self.sum = self.sum - old;
+        }
+        self.deque.push_back(val).expect("window has room");
+        self.sum = self.sum + val;
+        self.sum
+    }
+
+    fn sum(&self) -> T { self.sum }
+    fn len(&self) -> usize { self.deque.len() }
+}
+
+/// Mean of the last `k` samples.
+struct MovingAverage {
+    window: MovingSum<f64>,
+}
+
+impl MovingAverage {
+    fn new(k: usize) -> Self {
+        Self { window: MovingSum::new(k) }
+    }
+
+    // Add a sample, returning the average of the last `k` samples (fewer
+    // at the start).
+    fn push(&mut self, val: f64) -> f64 {
+        let sum = self.window.push(val);
+        sum / self.window.len() as f64
+    }
+}
+
+fn moving_sum<T>(items: &[T], k: usize) -> Vec<T>
+where
+    T: Copy + Default + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+{
+    let mut window = MovingSum::new(k);
+    full_windows(items.to_vec(), k, |x| window.push(x))
+}
+
+fn moving_average(items: &[f64], k: usize) -> Vec<f64> {
+    let mut window = MovingAverage::new(k);
+    full_windows(items.to_vec(), k, |x| window.push(x))
+}
+
+// FIRST MATCH.
+
+/// The oldest of the last `k` samples matching a condition. The deque
+/// holds the indexes of the matching samples still in the window.
+struct FirstMatch<T, F: Fn(&T) -> bool> {
+    k: usize,
+    seen: usize,
+    matches: Deque<(usize, T)>,
+    pred: F,
+}
+
+impl<T: Clone, F: Fn(&T) -> bool> FirstMatch<T, F> {
+    fn new(k: usize, pred: F) -> Self {
+        assert!(k > 0, "window length must be positive");
+        Self { k, seen: 0, matches: Deque::new(k), pred }
+    }
+
+    // Add a sample, returning the first matching one of the last `k`.
+    fn push(&mut self, val: T) -> Option<T> {
+        if let Some(&(index, _)) = self.matches.front() {
+            if index + self.k <= self.seen {
+                self.matches.pop_front();
+            }
+        }
+        if (self.pred)(&val) {
+            self.matches.push_back((self.seen, val)).expect("window has room");
+        }
+        self.seen += 1;
+        self.matches.front().map(|entry| entry.1.clone())
+    }
+}
+
+fn first_matching<T: Clone, F: Fn(&T) -> bool>(items: &[T], k: usize, pred: F) -> Vec<Option<T>> {
+    let mut window = FirstMatch::new(k, pred);
+    full_windows(items.to_vec(), k, |x| window.push(x))
+}
+
+// The first negative number in every window of `k`, None if there is none.
+fn first_negative(items: &[i64], k: usize) -> Vec<Option<i64>> {
+    first_matching(items, k, |&x| x < 0)
+}
+
+fn main() {
+    latency_ops();
+    average_ops();
+    negative_ops();
+    check_ops();
+
+    fn latency_ops() {
+        println!("ROLLING LATENCY");
+        let samples = [12, 15, 9, 40, 11, 10, 13, 8, 8, 30];
+        println!("samples:       {:?}", samples);
+        println!("max of 3:      {:?}", sliding_max(&samples, 3));
+        println!("min of 3:      {:?}", sliding_min(&samples, 3));
+        // The same, one sample at a time as a dashboard receives them.
+        let mut rolling = SlidingExtreme::max(4);
+        let streamed: Vec<i32> = samples.iter().map(|&x| rolling.push(x)).collect();
+        println!("streamed max of 4: {:?}", streamed);
+        println!("=========================");
+    }
+
+    fn average_ops() {
+        println!("MOVING SUM AND AVERAGE");
+        let requests = [5, 3, 8, 2, 7, 1];
+        println!("sum of 3:     {:?}", moving_sum(&requests, 3));
+        let cpu = [0.5, 0.75, 1.0, 0.25, 0.5];
+        println!("average of 2: {:?}", moving_average(&cpu, 2));
+        let mut avg = MovingAverage::new(3);
+        print!("streamed average of 3: ");
+        for x in cpu.iter() {
+            print!("{:.3}, ", avg.push(*x));
+        }
+        println!("\n=========================");
+    }
+
+    fn negative_ops() {
+        println!("FIRST NEGATIVE IN EVERY WINDOW");
+        let items = [12, -1, -7, 8, -15, 30, 16, 28];
+        println!("items: {:?}", items);
+        println!("k = 3: {:?}", first_negative(&items, 3));
+        let words = ["ok", "ok", "ERROR: disk", "ok", "WARN", "ERROR: net", "ok"];
+        println!("first error in 3 log lines: {:?}", first_matching(&words, 3, |w| w.starts_with("ERROR")));
+        println!("=========================");
+    }
+
+    fn check_ops() {
+        println!("CHECK AGAINST BRUTE FORCE");
+        let mut seed: u64 = 0x2545F4914F6CDD1D;
+        let mut next = move || {
+            seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..200 {
+            let n = 1 + (next() % 60) as usize;
+            let k = 1 + (next() % n as u64) as usize;
+            let items: Vec<i64> = (0..n).map(|_| (next() % 21) as i64 - 10).collect();
+            let windows = items.windows(k);
+            assert_eq!(sliding_max(&items, k), windows.clone().map(|w| *w.iter().max().unwrap()).collect::<Vec<_>>());
+            assert_eq!(sliding_min(&items, k), windows.clone().map(|w| *w.iter().min().unwrap()).collect::<Vec<_>>());
+            assert_eq!(moving_sum(&items, k), windows.clone().map(|w| w.iter().sum()).collect::<Vec<i64>>());
+            assert_eq!(first_negative(&items, k), windows.map(|w| w.iter().cloned().find(|&x| x < 0)).collect::<Vec<_>>());
+        }
+        println!("200 random sequences agree with slice::windows");
+        println!("=========================");
+    }
+}
@@ -0,0 +1,374 @@
+//! Work-stealing deque.
+#![allow(warnings)] // Supress all warnings for the entire crate.
+/// # Chase-Lev deque
+/// The double-ended idea of deckrus.rs for a thread pool: the thread owning
+/// the deque pushes and pops tasks at the bottom like a stack, while other
+/// threads `steal` the oldest tasks from the top. The owner's side needs no
+/// compare-and-swap except when it races a thief for the last task.
+///
+/// Tasks live in a circular array indexed by `top` and `bottom`, which
+/// only grow. A full array is replaced by one twice as large. A thief may
+/// still be reading the old array, so old arrays are kept until the deque
+/// is dropped.
+///
+/// Follows "Correct and Efficient Work-Stealing for Weak Memory Models"
+/// (Lê, Pop, Cohen, Zappa Nardelli, 2013).
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+/// Result of `Stealer::steal`.
+#[derive(Debug, PartialEq)]
+enum Steal<T> {
+    // Nothing to steal.
+    Empty,
+    Success(T),
+    // Lost a race with another thread, trying again may succeed.
+    Retry,
+}
+
+// Circular array, its capacity is a power of two.
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(cap: usize) -> *mut Buffer<T> {
+        let slots = (0..cap).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect();
+        Box::into_raw(Box::new(Buffer { slots }))
+    }
+
+    fn cap(&self) -> isize {
+        self.slots.len() as isize
+    }
+
+    fn slot(&self, index: isize) -> *mut MaybeUninit<T> {
+        self.slots[(index & (self.cap() - 1)) as usize].get()
+    }
+
+    unsafe fn write(&self, index: isize, val: T) {
+        ptr::write(self.slot(index), MaybeUninit::new(val));
+    }
+
+    // A bitwise copy, only owned once the caller has claimed the index.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        ptr::read(self.slot(index))
+    }
+}
+
+struct Inner<T> {
+    top: AtomicIsize,    // Next index to steal
+    bottom: AtomicIsize, // Next index to push
+    buffer: AtomicPtr<Buffer<T>>,
+    // Replaced arrays, freed on drop.
+    old: Mutex<Vec<*mut Buffer<T>>>,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let top = *self.top.get_mut();
+        let bottom = *self.bottom.get_mut();
+        let buffer = *self.buffer.get_mut();
+        unsafe {
+            for index in top..bottom {
+                (*buffer).read(index).assume_init();
+            }
+            drop(Box::from_raw(buffer));
+            // Old arrays only hold copies of tasks, free the memory alone.
+            for old in self.old.get_mut().unwrap().drain(..) {
+                drop(Box::from_raw(old));
+            }
+        }
+    }
+}
+
+/// The owner's end: push and pop at the bottom. It can be sent to another
+/// thread but not shared.
+struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    // Two threads pushing at once would break the deque, keep it !Sync.
+    not_sync: PhantomData<Cell<()>>,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+
+/// A thief's end: steal from the top. Clone it for every thief.
+struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T> Worker<T> {
+    fn new() -> Self {
+        Self::with_capacity(32)
+    }
+
+    // Start with room for `cap` tasks, rounded up to a power of two. The
+    // array grows when needed.
+    fn with_capacity(cap: usize) -> Self {
+        let cap = cap.max(2).next_power_of_two();
+        Self {
+            not_sync: PhantomData,
+            inner: Arc::new(Inner {
+                top: AtomicIsize::new(0),
+                bottom: AtomicIsize::new(0),
+                buffer: AtomicPtr::new(Buffer::alloc(cap)),
+                old: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    fn stealer(&self) -> Stealer<T> {
+        Stealer { inner: Arc::clone(&self.inner) }
+    }
+
+    // A snapshot, thieves may change it right away.
+    fn len(&self) -> usize {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top.load(Ordering::Relaxed);
+        (bottom - top).max(0) as usize
+    }
+
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    fn push(&self, val: T) {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed);
+        let top = inner.top.load(Ordering::Acquire);
+        let mut buffer = inner.buffer.load(Ordering::Relaxed);
+        unsafe {
+            if bottom - top >= (*buffer).cap() {
+                buffer = self.grow(buffer, top, bottom);
+            }
+            (*buffer).write(bottom, val);
+        }
+        // The task must be visible before the new bottom.
+        fence(Ordering::Release);
+        inner.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    // Take the newest task.
+    fn pop(&self) -> Option<T> {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = inner.buffer.load(Ordering::Relaxed);
+        // Reserve the bottom task before looking at top, thieves see the
+        // reservation through the SeqCst fences.
+        inner.bottom.store(bottom, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let top = inner.top.load(Ordering::Relaxed);
+        if top > bottom {
+            // Empty.
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+        let val = unsafe { (*buffer).read(bottom) };
+        if top == bottom {
+            // The last task: a thief may be after it too.
+            let won = inner.top
+                .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        Some(unsafe { val.assume_init() })
+    }
+
+    // Copy the live tasks into an array twice as large.
+    unsafe fn grow(&self, old: *mut Buffer<T>, top: isize, bottom: isize) -> *mut Buffer<T> {
+        let new = Buffer::alloc(2 * (*old).cap() as usize);
+        for index in top..bottom {
+            ptr::copy_nonoverlapping((*old).slot(index), (*new).slot(index), 1);
+        }
+        self.inner.old.lock().unwrap().push(old);
+        self.inner.buffer.store(new, Ordering::Release);
+        new
+    }
+}
+
+impl<T> Stealer<T> {
+    // Take the oldest task.
+    fn steal(&self) -> Steal<T> {
+        let inner = &*self.inner;
+        let top = inner.top.load(Ordering::Acquire);
+        fence(Ordering::SeqCst);
+        let bottom = inner.bottom.load(Ordering::Acquire);
+        if top >= bottom {
+            return Steal::Empty;
+        }
+        // Read before claiming: once top moves on, the owner may reuse the
+        // slot. The copy is only kept if the claim succeeds.
+        let buffer = inner.buffer.load(Ordering::Acquire);
+        let val = unsafe { (*buffer).read(top) };
+        if inner.top.compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed).is_err() {
+            return Steal::Retry;
+        }
+        Steal::Success(unsafe { val.assume_init() })
+    }
+
+    fn is_empty(&self) -> bool {
+        let top = self.inner.top.load(Ordering::Acquire);
+        let bottom = self.inner.bottom.load(Ordering::Acquire);
+        top >= bottom
+    }
+}
+
+fn main() {
+    basic_ops();
+    steal_ops();
+    pool_ops();
+
+    fn basic_ops() {
+        println!("BASIC OPERATIONS");
+        let worker = Worker::with_capacity(2);
+        let stealer = worker.stealer();
+        for task in ["parse", "check", "emit", "link"] {
+            worker.push(task);
+        }
+        println!("len after growing past 2: {}", worker.len());
+        println!("owner pops newest: {:?}", worker.pop());
+        println!("thief steals oldest: {:?}", stealer.steal());
+        println!("pop: {:?}, pop: {:?}, pop: {:?}", worker.pop(), worker.pop(), worker.pop());
+        println!("steal from empty: {:?}", stealer.steal());
+        println!("================================================");
+    }
+
+    fn steal_ops() {
+        println!("CONCURRENT STEALING");
+        // Every task must be taken exactly once, by the owner or a thief.
+        const TASKS: usize = 200_000;
+        let taken: Arc<Vec<AtomicBool>> = Arc::new((0..TASKS).map(|_| AtomicBool::new(false)).collect());
+        let done = Arc::new(AtomicBool::new(false));
+        let worker: Worker<usize> = Worker::with_capacity(4);
+        let stolen = Arc::new(AtomicUsize::new(0));
+        let thieves: Vec<_> = (0..3).map(|_| {
+            let stealer = worker.stealer();
+            let (taken, done, stolen) = (Arc::clone(&taken), Arc::clone(&done), Arc::clone(&stolen));
+            thread::spawn(move || {
+                let mut retries = 0;
+                loop {
+                    match stealer.steal() {
+                        Steal::Success(task) => {
+                            assert!(!taken[task].swap(true, Ordering::Relaxed), "task {task} taken twice");
+                            stolen.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Steal::Retry => retries += 1,
+                        Steal::Empty if done.load(Ordering::Acquire) => break,
+                        Steal::Empty => thread::yield_now(),
+                    }
+                }
+                retries
+            })
+        }).collect();
+        for task in 0..TASKS {
+            worker.push(task);
+            // Pop now and then so owner and thieves fight over the bottom.
+            if task % 3 == 0 {
+                if let Some(task) = worker.pop() {
+                    assert!(!taken[task].swap(true, Ordering::Relaxed), "task {task} taken twice");
+                }
+            }
+        }
+        while let Some(task) = worker.pop() {
+            assert!(!taken[task].swap(true, Ordering::Relaxed), "task {task} taken twice");
+        }
+        done.store(true, Ordering::Release);
+        let retries: usize = thieves.into_iter().map(|t| t.join().unwrap()).sum();
+        assert!(taken.iter().all(|t| t.load(Ordering::Relaxed)));
+        println!("{} tasks each taken once, {} stolen, {} retries",
+                 TASKS, stolen.load(Ordering::Relaxed), retries);
+        println!("================================================");
+    }
+
+    fn pool_ops() {
+        println!("SPLITTING WORK");
+        // Sum 1..=n on four threads, each with a deque of its own. A thread
+        // splits a range, keeps the halves on its deque and runs them newest
+        // first. Once its deque is empty it steals from the others, taking
+        // the oldest and largest ranges from their tops.
+        const THREADS: usize = 4;
+        let n: u64 = 5_000_000;
+        let workers: Vec<Worker<(u64, u64)>> = (0..THREADS).map(|_| Worker::new()).collect();
+        let stealers: Arc<Vec<Stealer<(u64, u64)>>> = Arc::new(workers.iter().map(|w| w.stealer()).collect());
+        workers[0].push((1, n + 1));
+        let remaining = Arc::new(AtomicUsize::new(1));
+        let total = Arc::new(AtomicUsize::new(0));
+        let start = Arc::new(Barrier::new(THREADS));
+        let run = |range: (u64, u64), worker: &Worker<(u64, u64)>, remaining: &AtomicUsize, total: &AtomicUsize| {
+            let (lo, mut hi) = range;
+            // Split off upper halves while the range is large.
+            while hi - lo > 10_000 {
+                let mid = lo + (hi - lo) / 2;
+                remaining.fetch_add(1, Ordering::Relaxed);
+                worker.push((mid, hi));
+                hi = mid;
+            }
+            total.fetch_add((lo..hi).sum::<u64>() as usize, Ordering::Relaxed);
+            remaining.fetch_sub(1, Ordering::AcqRel);
+        };
+        let threads: Vec<_> = workers.into_iter().enumerate().map(|(me, worker)| {
+            let (stealers, remaining, total, start) =
+                (Arc::clone(&stealers), Arc::clone(&remaining), Arc::clone(&total), Arc::clone(&start));
+            thread::spawn(move || {
+                start.wait();
+                let (mut ran, mut stolen) = (0, 0);
+                while remaining.load(Ordering::Acquire) > 0 {
+                    let range = worker.pop().or_else(|| {
+                        let found = steal_from(&stealers, me);
+                        stolen += found.is_some() as usize;
+                        found
+                    });
+                    if let Some(range) = range {
+                        ran += 1;
+                        run(range, &worker, &remaining, &total);
+                    }
+                    // Let the other threads in, so the work spreads even on
+                    // a single core.
+                    thread::yield_now();
+                }
+                (ran, stolen)
+            })
+        }).collect();
+        let counts: Vec<(usize, usize)> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(total.load(Ordering::Relaxed) as u64, n * (n + 1) / 2);
+        let stolen: usize = counts.iter().map(|c| c.1).sum();
+        assert!(stolen > 0, "no range was stolen");
+        println!("sum 1..={} = {}", n, total.load(Ordering::Relaxed));
+        for (me, (ran, stolen)) in counts.iter().enumerate() {
+            println!("thread {me}: ran {ran} ranges, {stolen} of them stolen");
+        }
+        println!("================================================");
+
+        // Try every other thread's deque once, starting after our own.
+        fn steal_from(stealers: &[Stealer<(u64, u64)>], me: usize) -> Option<(u64, u64)> {
+            for i in 1..stealers.len() {
+                let victim = &stealers[(me + i) % stealers.len()];
+                loop {
+                    match victim.steal() {
+                        Steal::Success(range) => return Some(range),
+                        Steal::Retry => continue,
+                        Steal::Empty => break,
+                    }
+                }
+            }
+            None
+        }
+    }
+}